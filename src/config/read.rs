@@ -14,14 +14,84 @@ pub struct Network {
     pub listen_addr: String,
 }
 
+#[derive(Deserialize, Default)]
+pub struct Tokenizer {
+    pub dict_path: Option<String>,
+    pub user_dict_path: Option<String>,
+    #[serde(default)]
+    pub stemming: bool,
+    #[serde(default)]
+    pub stop_words: Vec<String>,
+    #[serde(default)]
+    pub traditional_to_simplified: bool,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Boost {
+    pub title: f32,
+    pub tags: f32,
+    pub category: f32,
+    pub content: f32,
+    pub recency: f32,
+    pub recency_half_life: f32,
+}
+
+impl Default for Boost {
+    fn default() -> Boost {
+        Boost {
+            title: 2.0,
+            tags: 1.5,
+            category: 1.0,
+            content: 1.0,
+            recency: 0.0,
+            recency_half_life: 365.0,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Paging {
+    pub default_size: usize,
+    pub max_size: usize,
+}
+
+impl Default for Paging {
+    fn default() -> Paging {
+        Paging {
+            default_size: 8,
+            max_size: 50,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct Search {
+    pub synonyms_path: Option<String>,
+    #[serde(default)]
+    pub boost: Boost,
+    #[serde(default)]
+    pub paging: Paging,
+}
+
 #[derive(Deserialize)]
 pub struct Config {
     pub database: Database,
     pub network: Network,
+    #[serde(default)]
+    pub tokenizer: Tokenizer,
+    #[serde(default)]
+    pub search: Search,
 }
 
 pub fn read_config(path: String) -> Config {
     // let path = env::var("CONFIG");
     let contents = read_to_string(Path::new(&path)).expect("No config file found.");
-    toml::from_str(&contents).unwrap()
+    let config: Config = toml::from_str(&contents).unwrap();
+    assert!(
+        config.search.boost.recency_half_life > 0.0,
+        "`recency_half_life` must be greater than 0."
+    );
+    config
 }
@@ -60,9 +60,10 @@ fn extract_integer_list(raw_params: &[u8]) -> (Vec<i64>, &[u8]) {
     (integers, &raw_params[length + LENGTH_SIZE + offset..])
 }
 
-pub fn extract_params<T: Read>(
-    stream: &mut T,
-) -> Result<(Vec<i64>, Vec<i64>, Vec<String>, Vec<String>), ()> {
+// pages, range, terms, keywords and the optional `key:value` options
+type Params = (Vec<i64>, Vec<i64>, Vec<String>, Vec<String>, Vec<String>);
+
+pub fn extract_params<T: Read>(stream: &mut T) -> Result<Params, ()> {
     let length = extract_length(stream);
     if 0 == length {
         return Err(());
@@ -73,11 +74,16 @@ pub fn extract_params<T: Read>(
     let (pages, raw_params) = extract_integer_list(raw_params);
     let (range, raw_params) = extract_integer_list(raw_params);
     let (terms, raw_params) = extract_string_list(raw_params);
-    let (q, _) = extract_string_list(raw_params);
+    let (q, raw_params) = extract_string_list(raw_params);
+    let options = match raw_params.is_empty() {
+        true => vec![],
+        false => extract_string_list(raw_params).0,
+    };
     Ok((
         pages,
         range,
         terms,
         q.into_iter().filter(|x| !x.is_empty()).collect(),
+        options,
     ))
 }
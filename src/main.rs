@@ -10,28 +10,49 @@ use std::{
     net::TcpListener,
     path::Path,
 };
-use tantivy::collector::Count;
-use time::{format_description, Date};
+use tantivy::{collector::TopDocs, query::Explanation, Document, SnippetGenerator};
 
 use std::os::unix::net::UnixListener;
 use std::{process::exit, thread};
 mod config;
 mod search;
-use search::QuerySchema;
+use search::{
+    facets::{self, Facets},
+    snippet::Highlighted,
+    QuerySchema, RequestType, SearchOptions, SnippetFormat, SnippetOptions, Sort,
+};
 mod ipc;
 mod migrate;
 mod tokenizer;
 
-static DEFAULT_MAX_SIZE: usize = 8;
-
+/// A matching post, with only the fields the request asked for.
 #[derive(Serialize)]
 struct Hit {
-    url: String,
-    date: String,
-    title: String,
-    snippet: String,
-    category: String,
-    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snippet: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tags: Option<Vec<String>>,
+    /// the full text, only when asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    /// char ranges of the highlights in `title`, with `format:text`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title_highlights: Option<Vec<[usize; 2]>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snippet_highlights: Option<Vec<[usize; 2]>>,
+    /// with `debug`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    explanation: Option<Explanation>,
 }
 
 #[derive(Serialize)]
@@ -40,111 +61,326 @@ struct Err {
 }
 
 #[derive(Serialize)]
-struct Response {
+struct Response<T> {
     count: usize,
-    data: Vec<Hit>,
+    data: Vec<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suggestion: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    facets: Option<Facets>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cursor: Option<String>,
 }
 
-fn execute(
+fn error(err_msg: String) -> String {
+    format!("{}\n", serde_json::json!(Err { err_msg }))
+}
+
+// the html, or the plain text and its highlight ranges
+fn render(
+    highlighted: Option<Highlighted>,
+    options: &SnippetOptions,
+) -> (Option<String>, Option<Vec<[usize; 2]>>) {
+    let highlighted = match highlighted {
+        Some(highlighted) => highlighted,
+        None => return (None, None),
+    };
+    match options.format {
+        SnippetFormat::Html => {
+            let (prefix, postfix) = &options.highlight;
+            (Some(highlighted.to_html(prefix, postfix)), None)
+        }
+        SnippetFormat::Text => (Some(highlighted.text), Some(highlighted.ranges)),
+    }
+}
+
+// the stored fields the request asks for, the title as it is
+fn stored_hit(query_schema: &QuerySchema, doc: &Document, options: &SearchOptions) -> Hit {
+    let fields = &query_schema.fields;
+    let text = |field| doc.get_first(field).and_then(|v| v.as_text()).unwrap_or("");
+    let wants = |name| options.wants(name).then_some(());
+    Hit {
+        url: wants("url").map(|_| text(fields.url).to_string()),
+        date: wants("date").map(|_| {
+            doc.get_first(fields.date)
+                .and_then(|v| v.as_date())
+                .expect("Err date")
+                .into_utc()
+                .to_string()
+        }),
+        title: wants("title").map(|_| text(fields.title).to_string()),
+        snippet: None,
+        category: wants("category").map(|_| text(fields.category).to_string()),
+        content: wants("content").map(|_| text(fields.content).to_string()),
+        tags: wants("tags").map(|_| {
+            doc.get_all(fields.tags)
+                .map(|x| x.as_text().expect("Err tag").to_string())
+                .collect()
+        }),
+        title_highlights: None,
+        snippet_highlights: None,
+        score: None,
+        explanation: None,
+    }
+}
+
+fn make_hit(
+    query_schema: &QuerySchema,
+    doc: &Document,
+    title_gen: &Option<SnippetGenerator>,
+    content_gen: &Option<SnippetGenerator>,
+    options: &SearchOptions,
+) -> Hit {
+    let fields = &query_schema.fields;
+    let wants = |name| options.wants(name).then_some(());
+    let (title, title_highlights) = render(
+        wants("title").map(|_| {
+            query_schema.make_snippet_value(
+                title_gen,
+                doc.get_first(fields.title).expect("Err title"),
+                &SnippetOptions {
+                    fragments: 1,
+                    ..Default::default()
+                },
+            )
+        }),
+        &options.snippet,
+    );
+    let (snippet, snippet_highlights) = render(
+        wants("snippet").map(|_| {
+            query_schema.make_snippet_value(
+                content_gen,
+                doc.get_first(fields.content).expect("Err content"),
+                &options.snippet,
+            )
+        }),
+        &options.snippet,
+    );
+    Hit {
+        title,
+        snippet,
+        title_highlights,
+        snippet_highlights,
+        ..stored_hit(query_schema, doc, options)
+    }
+}
+
+fn dispatch(
     pages: Vec<i64>,
     range: Vec<i64>,
     terms: Vec<String>,
     q: Vec<String>,
+    options: Vec<String>,
+    query_schema: &QuerySchema,
+) -> String {
+    let options = match SearchOptions::parse(&options) {
+        Ok(options) => options,
+        Err(err_msg) => return error(err_msg),
+    };
+    match options.request {
+        RequestType::Search => execute(pages, range, terms, q, &options, query_schema),
+        RequestType::Suggest => suggest(pages, q, query_schema),
+        RequestType::Related => related(pages, q, &options, query_schema),
+        RequestType::Archive => archive(pages, q, options, query_schema),
+        RequestType::Get => get(q, &options, query_schema),
+    }
+}
+
+fn suggest(pages: Vec<i64>, q: Vec<String>, query_schema: &QuerySchema) -> String {
+    let (_, limit) = query_schema.page_size(&pages);
+    let data = query_schema.suggest(&q.join(" "), limit);
+    serde_json::json!(Response {
+        count: data.len(),
+        data,
+        suggestion: None,
+        facets: None,
+        cursor: None,
+    })
+    .to_string()
+}
+
+fn related(
+    pages: Vec<i64>,
+    q: Vec<String>,
+    options: &SearchOptions,
     query_schema: &QuerySchema,
 ) -> String {
-    let kq = query_schema.make_keyword_query(q.clone());
-    if kq.is_err() {
-        return format!("{{\"err_msg\": \"{}\"}}\n", kq.unwrap_err().to_string());
+    let (_, limit) = query_schema.page_size(&pages);
+    let related_query = match q
+        .first()
+        .and_then(|url| query_schema.make_related_query(url))
+    {
+        Some(related_query) => related_query,
+        None => return error("Post not found.".to_string()),
+    };
+    let searcher = query_schema.reader.searcher();
+    let data: Vec<Hit> = searcher
+        .search(&related_query, &TopDocs::with_limit(limit))
+        .expect("Search Failed")
+        .into_iter()
+        .map(|(_score, doc_addr)| {
+            let doc = searcher.doc(doc_addr).expect("Not Found Document Address");
+            make_hit(query_schema, &doc, &None, &None, options)
+        })
+        .collect();
+    serde_json::json!(Response {
+        count: data.len(),
+        data,
+        suggestion: None,
+        facets: None,
+        cursor: None,
+    })
+    .to_string()
+}
+
+fn get(q: Vec<String>, options: &SearchOptions, query_schema: &QuerySchema) -> String {
+    let doc = match q.first().and_then(|url| query_schema.find_url(url)) {
+        Some(doc) => doc,
+        None => return error("Post not found.".to_string()),
+    };
+    let mut hit = stored_hit(query_schema, &doc, options);
+    // a snippet of the content only when asked for by name
+    let named = |name| options.fields.iter().flatten().any(|f| f == name);
+    if named("snippet") {
+        let content = doc.get_first(query_schema.fields.content);
+        (hit.snippet, hit.snippet_highlights) = render(
+            content.map(|c| query_schema.make_snippet_value(&None, c, &options.snippet)),
+            &options.snippet,
+        );
     }
-    let mut box_qs = kq.unwrap();
+    serde_json::json!(Response {
+        count: 1,
+        data: vec![hit],
+        suggestion: None,
+        facets: None,
+        cursor: None,
+    })
+    .to_string()
+}
+
+fn archive(
+    pages: Vec<i64>,
+    q: Vec<String>,
+    mut options: SearchOptions,
+    query_schema: &QuerySchema,
+) -> String {
+    let month = match q.first() {
+        Some(month) => month,
+        None => {
+            let data = query_schema.archive(&options);
+            return serde_json::json!(Response {
+                count: data.iter().map(|y| y.count as usize).sum(),
+                data,
+                suggestion: None,
+                facets: None,
+                cursor: None,
+            })
+            .to_string();
+        }
+    };
+    let is_month = |m: &str| {
+        m.len() == 7
+            && m.bytes().enumerate().all(|(i, b)| match i {
+                4 => b == b'-',
+                _ => b.is_ascii_digit(),
+            })
+    };
+    if !is_month(month) {
+        return error(format!(
+            "Invalid archive month `{}`, expected YYYY-MM.",
+            month
+        ));
+    }
+    options.date = Some(month.to_owned());
+    execute(pages, vec![], vec![], vec![], &options, query_schema)
+}
+
+fn execute(
+    pages: Vec<i64>,
+    range: Vec<i64>,
+    terms: Vec<String>,
+    q: Vec<String>,
+    options: &SearchOptions,
+    query_schema: &QuerySchema,
+) -> String {
+    let kq = query_schema.make_keyword_query(q.clone(), options);
+    let mut box_qs = match kq {
+        Ok(qs) => qs,
+        Err(err_msg) => return error(err_msg),
+    };
+    let highlight = query_schema.make_highlight_query(&q, options);
+    let only_excluding = !box_qs.is_empty() && highlight.is_none();
+    query_schema.pinyin_fallback(&q, &mut box_qs);
     query_schema.make_terms_query(terms, &mut box_qs);
-    query_schema.make_date_query(range, &mut box_qs);
+    if let Err(err_msg) = query_schema.make_date_query(range, &mut box_qs) {
+        return error(err_msg);
+    }
+    if let Some(date) = &options.date {
+        match query_schema.make_date_range(date, options) {
+            Ok(date_query) => box_qs.push(date_query),
+            Err(err_msg) => return error(err_msg),
+        }
+    }
     if box_qs.len() == 0 {
         return format!("{{\"err_msg\": \"It is forbidden queries that are empty.\"}}\n");
     }
-    let content_gen = query_schema.make_snippet_gen(&box_qs[0], query_schema.fields.content);
-    let title_gen = query_schema.make_snippet_gen(&box_qs[0], query_schema.fields.title);
+    // excluding keywords need a filter to exclude from
+    if only_excluding && box_qs.len() == 1 {
+        return error("It is forbidden queries that are only `excluding`.".to_string());
+    }
+    let make_gen = |field, length| {
+        highlight
+            .as_ref()
+            .and_then(|hq| query_schema.make_snippet_gen(hq, field, length))
+    };
+    let content_gen = make_gen(query_schema.fields.content, options.snippet.length);
+    let title_gen = make_gen(query_schema.fields.title, 300);
     let bool_qs = query_schema.make_bool_query(box_qs);
     let searcher = query_schema.reader.searcher();
 
-    let mut results: Vec<Hit> = Vec::with_capacity(DEFAULT_MAX_SIZE);
-    let (results, num) = if !q.is_empty() {
-        let (top_docs, num) = searcher
-            .search(&bool_qs, &(query_schema.make_paginate(pages), Count))
-            .expect("Search Failed");
-        for (_score, doc_addr) in top_docs {
-            let doc = searcher.doc(doc_addr).expect("Not Found Document Address");
-            let values = doc.get_sorted_field_values();
-            let title = query_schema.make_snippet_value(&title_gen, &doc, values[0].1[0]);
-            let snippet = query_schema.make_snippet_value(&content_gen, &doc, values[1].1[0]);
-            results.push(Hit {
-                url: values[5].1[0].as_text().expect("Err Url").to_string(),
-                date: values[2].1[0]
-                    .as_date()
-                    .expect("Err date")
-                    .into_utc()
-                    .to_string(),
-                category: values[4].1[0].as_text().expect("Err Category").to_string(),
-                tags: values[3]
-                    .1
-                    .to_vec()
-                    .into_iter()
-                    .map(|x| x.as_text().expect("Err tag").to_string())
-                    .collect(),
-                title,
-                snippet,
-            });
-        }
-        (results, num)
-    } else {
-        let (top_docs, num) = searcher
-            .search(
-                &bool_qs,
-                &(query_schema.make_paginate_with_sort(pages), Count),
-            )
-            .expect("Search Failed");
-        for (_score, doc_addr) in top_docs {
+    let sort = options.sort.unwrap_or(match q.is_empty() {
+        true => Sort::Newest,
+        false => Sort::Relevance,
+    });
+    let page = match query_schema.search_page(&bool_qs, pages, sort, options.cursor.as_deref()) {
+        Ok(page) => page,
+        Err(err_msg) => return error(err_msg),
+    };
+    let num = page.count;
+    let results: Vec<Hit> = page
+        .docs
+        .into_iter()
+        .map(|doc_addr| {
             let doc = searcher.doc(doc_addr).expect("Not Found Document Address");
-            let values = doc.get_sorted_field_values();
-            let title = query_schema.make_snippet_value(&title_gen, &doc, values[0].1[0]);
-            let snippet = query_schema.make_snippet_value(&content_gen, &doc, values[1].1[0]);
-            results.push(Hit {
-                url: values[5].1[0].as_text().expect("Err Url").to_string(),
-                date: values[2].1[0]
-                    .as_date()
-                    .expect("Err date")
-                    .into_utc()
-                    .to_string(),
-                category: values[4].1[0].as_text().expect("Err Category").to_string(),
-                tags: values[3]
-                    .1
-                    .to_vec()
-                    .into_iter()
-                    .map(|x| x.as_text().expect("Err tag").to_string())
-                    .collect(),
-                title,
-                snippet,
-            });
-        }
-        (results, num)
+            let mut hit = make_hit(query_schema, &doc, &title_gen, &content_gen, options);
+            if options.debug {
+                hit.explanation = query_schema.explain(&bool_qs, doc_addr, sort);
+                hit.score = hit.explanation.as_ref().map(|e| e.value());
+            }
+            hit
+        })
+        .collect();
+    let suggestion = match num {
+        0 => query_schema.did_you_mean(&q),
+        _ => None,
     };
     let se_result = serde_json::json!(Response {
         count: num,
         data: results,
+        suggestion,
+        facets: match options.facets {
+            true => Some(facets::count(&searcher, &bool_qs)),
+            false => None,
+        },
+        cursor: page.cursor,
     });
     se_result.to_string()
 }
 
 fn handle_client<T: Write + Read + Debug>(stream: &mut T, qs: QuerySchema) {
     println!("new client: {:?}", stream);
-    loop {
-        let params: (Vec<i64>, Vec<i64>, Vec<String>, Vec<String>);
-        match ipc::extract_params(stream) {
-            Ok(p) => params = p,
-            Err(_) => break,
-        }
-        let (p, r, t, q) = params;
-        let result = execute(p, r, t, q, &qs);
+    while let Ok((p, r, t, q, o)) = ipc::extract_params(stream) {
+        let result = dispatch(p, r, t, q, o, &qs);
         let result = encode_result(result);
         match stream.write_all(&result) {
             Ok(_) => (),
@@ -174,28 +410,13 @@ where
     }
 }
 
-fn transform_date(date_str: &str) -> i64 {
-    if date_str == "" {
-        return 0;
-    }
-    let date_format = format_description::parse("[year]-[month]-[day]").unwrap();
-
-    match Date::parse(date_str, &date_format) {
-        Ok(date) => date.midnight().assume_utc().unix_timestamp(),
-        Err(e) => {
-            println!("Error parsing date `{}`, {}", date_str, e);
-            0
-        }
-    }
-}
-
 fn dev_accept(socket: &Network, qs: QuerySchema) {
     let tcp = TcpListener::bind(&socket.listen_addr).expect("Bind to port error");
     for stream in tcp.incoming().into_iter() {
         match stream {
             Ok(mut stream) => {
                 stream
-                    .write_all("Arguments: Page, Range, Tags, Keywords\r\n> ".as_bytes())
+                    .write_all("Arguments: Page, Range, Tags, Keywords[, Options]\r\n> ".as_bytes())
                     .expect("Failed connect");
                 loop {
                     let mut reader = BufReader::new(stream.try_clone().unwrap());
@@ -204,16 +425,21 @@ fn dev_accept(socket: &Network, qs: QuerySchema) {
                     let raw = resp.strip_suffix("\r\n").expect("failed ");
                     let args: Vec<&str> = raw.split(",").collect();
                     let mut result = "Invalid Arguments. ".to_owned();
-                    if args.len() == 4 {
-                        result = execute(
+                    if args.len() == 4 || args.len() == 5 {
+                        let mut options: Vec<String> = args
+                            .get(4)
+                            .map(|o| o.split(" ").map(|s| s.to_string()).collect())
+                            .unwrap_or_default();
+                        // `2023-01~2023-03`, `2023~` or `last 30 days`
+                        if !args[1].trim().is_empty() {
+                            options.push(format!("date:{}", args[1].trim().replace('~', "..")));
+                        }
+                        result = dispatch(
                             args[0]
                                 .split("-")
-                                .map(|s| s.parse().unwrap())
-                                .collect::<Vec<_>>(),
-                            args[1]
-                                .split("~")
-                                .map(|d| transform_date(d))
+                                .filter_map(|s| s.parse().ok())
                                 .collect::<Vec<_>>(),
+                            vec![],
                             args[2]
                                 .split(" ")
                                 .map(|s| s.to_string())
@@ -223,6 +449,7 @@ fn dev_accept(socket: &Network, qs: QuerySchema) {
                                 .map(|s| s.to_string())
                                 .filter(|s| !s.is_empty())
                                 .collect::<Vec<_>>(),
+                            options,
                             &qs,
                         );
                     }
@@ -269,20 +496,26 @@ fn print_usage(program: String) {
 
 fn run(config_path: String, instruction: &str) {
     let config = config::read_config(config_path);
+    let analyzer = tokenizer::build_analyzer(&config.tokenizer);
     migrate::create_dir(&config.database.tantivy_db);
-    init_schema(&config.database.tantivy_db, &config.database.atom_url);
+    init_schema(
+        &config.database.tantivy_db,
+        &config.database.atom_url,
+        &analyzer,
+    );
     migrate::scheduled_load_schema(
         &config.database.tantivy_db,
         config.database.atom_url,
         config.database.update_interval,
+        analyzer.clone(),
     );
     match instruction {
         "run" => {
-            let qs = QuerySchema::new(&config.database.tantivy_db);
+            let qs = QuerySchema::new(&config.database.tantivy_db, analyzer, &config.search);
             socket_accept(&config.network, qs);
         }
         "dev" => {
-            let qs = QuerySchema::new(&config.database.tantivy_db);
+            let qs = QuerySchema::new(&config.database.tantivy_db, analyzer, &config.search);
             dev_accept(&config.network, qs);
         }
         _ => (),
@@ -321,3 +554,80 @@ fn main() {
     let conf_path = locate_config_file();
     run(conf_path, &args[1]);
 }
+
+#[cfg(test)]
+fn test_json(result: String) -> serde_json::Value {
+    serde_json::from_str(&result).unwrap()
+}
+
+#[test]
+fn test_only_excluding() {
+    use search::query::{test_blog, test_index};
+    let qs = test_index(
+        vec![
+            test_blog(0, "Rust notes", "Rust is fast and safe.", "Code", &[]),
+            test_blog(1, "Go notes", "Go is simple.", "Code", &[]),
+        ],
+        &config::read::Search::default(),
+    );
+    let search = |terms: &[&str]| {
+        let terms = terms.iter().map(|t| t.to_string()).collect();
+        let q = vec!["-go".to_owned()];
+        test_json(execute(
+            vec![],
+            vec![],
+            terms,
+            q,
+            &SearchOptions::default(),
+            &qs,
+        ))
+    };
+    assert_eq!(
+        search(&[])["err_msg"],
+        "It is forbidden queries that are only `excluding`."
+    );
+    // with a filter nothing is highlighted and the snippet is the lead
+    let found = search(&["category:code"]);
+    assert_eq!(found["count"], 1);
+    assert_eq!(found["data"][0]["title"], "Rust notes");
+    assert_eq!(found["data"][0]["snippet"], "Rust is fast and safe.");
+}
+
+#[test]
+fn test_get() {
+    use search::query::{test_blog, test_index};
+    let qs = test_index(
+        vec![test_blog(
+            0,
+            "Rust notes",
+            "Rust is fast and safe.",
+            "Code",
+            &["rust"],
+        )],
+        &config::read::Search::default(),
+    );
+    let get = |url: &str, options: &[&str]| {
+        let options = options.iter().map(|o| o.to_string()).collect();
+        test_json(dispatch(
+            vec![],
+            vec![],
+            vec![],
+            vec![url.to_owned()],
+            options,
+            &qs,
+        ))
+    };
+    assert_eq!(
+        get("https://a/9", &["type:get"])["err_msg"],
+        "Post not found."
+    );
+    let post = &get("https://a/0", &["type:get"])["data"][0];
+    assert_eq!(post["title"], "Rust notes");
+    assert_eq!(post["tags"][0], "rust");
+    assert!(post.get("content").is_none());
+    assert!(post.get("snippet").is_none());
+    let post = &get("https://a/0", &["type:get", "fields:content,snippet"])["data"][0];
+    assert_eq!(post["content"], "Rust is fast and safe.");
+    assert_eq!(post["snippet"], "Rust is fast and safe.");
+    assert!(post.get("title").is_none());
+}
@@ -2,14 +2,15 @@ use tantivy::{
     collector::TopDocs,
     query::TermQuery,
     schema::{
-        Field, IndexRecordOption, Schema, Term, TextFieldIndexing, TextOptions, FAST, INDEXED,
-        STORED, STRING,
+        Facet, FacetOptions, Field, IndexRecordOption, Schema, Term, TextFieldIndexing,
+        TextOptions, FAST, INDEXED, STORED, STRING, TEXT,
     },
+    tokenizer::{LowerCaser, RawTokenizer, TextAnalyzer},
     DateTime, Document, Index, IndexWriter,
 };
 
 use super::fetch::fetch_atom;
-use crate::search::QuerySchema;
+use crate::tokenizer::pinyin::to_pinyin;
 use std::{fs, path::Path};
 
 pub fn create_dir(path: &str) {
@@ -59,50 +60,113 @@ pub fn exist_url(field: Field, url: String, index: Index) -> bool {
     return top_docs.len() > 0;
 }
 
+/// Lowercased facet of a `Programming/Rust` style path.
+pub fn to_facet(path: &str) -> Facet {
+    Facet::from_path(
+        path.split('/')
+            .map(|p| p.trim().to_lowercase())
+            .filter(|p| !p.is_empty()),
+    )
+}
+
+/// The `programming/rust` text of a facet.
+pub fn facet_text(facet: &Facet) -> String {
+    facet.to_path().join("/")
+}
+
 pub fn build_schema() -> Schema {
     let mut schema_builder = Schema::builder();
     let text_indeces = TextFieldIndexing::default()
         .set_tokenizer("UTF-8")
         .set_index_option(IndexRecordOption::WithFreqsAndPositions);
     let text_options = TextOptions::default().set_indexing_options(text_indeces);
-    schema_builder.add_text_field("title", text_options.clone() | STORED);
+    // the title as written in a fast column too, for suggestions
+    schema_builder.add_text_field("title", text_options.clone().set_fast(None) | STORED);
     schema_builder.add_text_field("content", text_options.clone() | STORED);
     // schema_builder.add_i64_field("date", INDEXED | STORED);
     // make date file type to date
     schema_builder.add_date_field("date", INDEXED | STORED | FAST);
-    schema_builder.add_text_field("tags", STRING | STORED);
-    schema_builder.add_text_field("category", STRING | STORED);
-    schema_builder.add_text_field("url", STRING | STORED);
+    schema_builder.add_text_field("tags", STRING | STORED | FAST);
+    // stored as written, matched lowercased
+    let lowercase = TextFieldIndexing::default()
+        .set_tokenizer("lowercase")
+        .set_index_option(IndexRecordOption::Basic);
+    schema_builder.add_text_field(
+        "category",
+        TextOptions::default()
+            .set_indexing_options(lowercase)
+            .set_fast(Some("raw"))
+            | STORED,
+    );
+    // `YYYY-MM` of the date, for counting posts per month
+    schema_builder.add_text_field("month", STRING | FAST);
+    schema_builder.add_text_field("url", STRING | STORED | FAST);
+    // `a/b/c` tags and category as facets, a filter on `a` matches them
+    schema_builder.add_facet_field("tags_facet", FacetOptions::default());
+    schema_builder.add_facet_field("category_facet", FacetOptions::default());
+    // full pinyin and initials of title and tags
+    schema_builder.add_text_field("pinyin", TEXT);
+    // lowercased title for sorting by title
+    schema_builder.add_text_field("title_sort", FAST);
     return schema_builder.build();
 }
 pub fn add_doc(schema: Schema, writer: &mut IndexWriter, blog: Blog) {
     let mut doc = Document::new();
+    let pinyin = schema.get_field("pinyin").unwrap();
+    for text in std::iter::once(&blog.title).chain(blog.tags.iter()) {
+        let (full, initials) = to_pinyin(text);
+        doc.add_text(pinyin, full);
+        doc.add_text(pinyin, initials);
+    }
+    doc.add_text(
+        schema.get_field("title_sort").unwrap(),
+        blog.title.to_lowercase(),
+    );
     doc.add_text(schema.get_field("title").unwrap(), blog.title);
     doc.add_text(schema.get_field("content").unwrap(), blog.content);
+    let date = blog.date.into_utc();
+    doc.add_text(
+        schema.get_field("month").unwrap(),
+        format!("{:04}-{:02}", date.year(), date.month() as u8),
+    );
     doc.add_date(schema.get_field("date").unwrap(), blog.date);
-    blog.tags
-        .iter()
-        .for_each(|tag| doc.add_text(schema.get_field("tags").unwrap(), tag.to_lowercase()));
-    doc.add_text(schema.get_field("category").unwrap(), blog.category);
+    for tag in blog.tags.iter().map(|t| to_facet(t)) {
+        doc.add_text(schema.get_field("tags").unwrap(), facet_text(&tag));
+        doc.add_facet(schema.get_field("tags_facet").unwrap(), tag);
+    }
+    let category = to_facet(&blog.category);
+    doc.add_text(schema.get_field("category").unwrap(), blog.category.trim());
+    if !category.is_root() {
+        doc.add_facet(schema.get_field("category_facet").unwrap(), category);
+    }
     doc.add_text(schema.get_field("url").unwrap(), blog.url);
     writer.add_document(doc).expect("add doc failed.");
 }
 
-pub fn build_index(path: &str, schema: Schema) -> Index {
+pub fn build_index(path: &str, schema: Schema, analyzer: &TextAnalyzer) -> Index {
     // check path is exist
     let index = match Path::new(path).join("meta.json").exists() {
         false => Index::create_in_dir(path, schema).unwrap(),
         true => Index::open_in_dir(path).unwrap(),
     };
+    register_tokenizers(&index, analyzer);
     index
-        .tokenizers()
-        .register("UTF-8", QuerySchema::tokenizer());
-    index
 }
 
-pub fn init_schema(path: &str, source: &str) {
+/// The tokenizers named in `build_schema`.
+pub fn register_tokenizers(index: &Index, analyzer: &TextAnalyzer) {
+    index.tokenizers().register("UTF-8", analyzer.clone());
+    index.tokenizers().register(
+        "lowercase",
+        TextAnalyzer::builder(RawTokenizer::default())
+            .filter(LowerCaser)
+            .build(),
+    );
+}
+
+pub fn init_schema(path: &str, source: &str, analyzer: &TextAnalyzer) {
     let schema = build_schema();
-    let index = build_index(path, schema.clone());
+    let index = build_index(path, schema.clone(), analyzer);
     let mut index_writer = index.writer(50_000_000).unwrap();
     let mut update_count = 0;
     for blog in fetch_atom(source) {
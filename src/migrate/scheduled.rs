@@ -3,12 +3,14 @@ use std::{
     time::Duration,
 };
 
+use tantivy::tokenizer::TextAnalyzer;
+
 use super::init::init_schema;
 
-pub fn scheduled_load_schema(path: &str, source: String, interval: u64) {
+pub fn scheduled_load_schema(path: &str, source: String, interval: u64, analyzer: TextAnalyzer) {
     let p_s = path.to_owned();
     thread::spawn(move || loop {
-        init_schema(&p_s, &source);
+        init_schema(&p_s, &source, &analyzer);
         sleep(Duration::from_secs(interval));
     });
 }
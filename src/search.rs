@@ -1,3 +1,14 @@
+pub mod archive;
+pub mod cursor;
+pub mod dates;
+pub mod facets;
+pub mod grammar;
+pub mod options;
 pub mod query;
+pub mod snippet;
+pub mod spelling;
+pub mod suggest;
+pub mod synonyms;
 
+pub use options::{RequestType, SearchOptions, SnippetFormat, SnippetOptions, Sort};
 pub use query::QuerySchema;
@@ -0,0 +1,87 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use tantivy::Searcher;
+use time::UtcOffset;
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct MonthCount {
+    pub month: u8,
+    pub count: u64,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct YearCount {
+    pub year: i32,
+    pub count: u64,
+    pub months: Vec<MonthCount>,
+}
+
+/// Posts per year and month of their `date` in `offset`, newest first.
+pub fn histogram(searcher: &Searcher, offset: UtcOffset) -> Vec<YearCount> {
+    let mut counts: BTreeMap<(i32, u8), u64> = BTreeMap::new();
+    for segment in searcher.segment_readers() {
+        let dates = match segment.fast_fields().date("date") {
+            Ok(dates) => dates,
+            Err(_) => continue,
+        };
+        for doc in segment.doc_ids_alive() {
+            if let Some(date) = dates.first(doc) {
+                let date = date.into_offset(offset);
+                *counts.entry((date.year(), date.month() as u8)).or_insert(0) += 1;
+            }
+        }
+    }
+    let mut years: Vec<YearCount> = Vec::new();
+    for ((year, month), count) in counts.into_iter().rev() {
+        match years.last_mut() {
+            Some(last) if last.year == year => {
+                last.count += count;
+                last.months.push(MonthCount { month, count });
+            }
+            _ => years.push(YearCount {
+                year,
+                count,
+                months: vec![MonthCount { month, count }],
+            }),
+        }
+    }
+    years
+}
+
+#[test]
+fn test_histogram() {
+    use tantivy::{
+        doc,
+        schema::{Schema, FAST, INDEXED},
+        DateTime, Index,
+    };
+    let mut builder = Schema::builder();
+    let date = builder.add_date_field("date", INDEXED | FAST);
+    let index = Index::create_in_ram(builder.build());
+    let mut writer = index.writer(15_000_000).unwrap();
+    // 2023-05-01, 2023-05-20, 2023-06-10 and 2022-12-31 20:00 UTC
+    for secs in [1682899200, 1684540800, 1686355200, 1672516800] {
+        writer
+            .add_document(doc!(date => DateTime::from_timestamp_secs(secs)))
+            .unwrap();
+    }
+    writer.commit().unwrap();
+    let searcher = index.reader().unwrap().searcher();
+    let months = |years: &[YearCount]| {
+        years
+            .iter()
+            .map(|y| (y.year, y.count, y.months.iter().map(|m| m.month).collect()))
+            .collect::<Vec<(i32, u64, Vec<u8>)>>()
+    };
+    assert_eq!(
+        months(&histogram(&searcher, UtcOffset::UTC)),
+        vec![(2023, 3, vec![6, 5]), (2022, 1, vec![12])]
+    );
+    // the last post is already in 2023 east of UTC
+    let east = UtcOffset::from_hms(8, 0, 0).unwrap();
+    assert_eq!(
+        months(&histogram(&searcher, east)),
+        vec![(2023, 4, vec![6, 5, 1])]
+    );
+}
@@ -0,0 +1,91 @@
+use std::cmp::Reverse;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+use super::options::Sort;
+
+/// A sort key that can travel inside a cursor token.
+pub trait SortKey: 'static + Send + Sync + Clone + PartialOrd {
+    fn to_text(&self) -> String;
+    fn from_text(text: &str) -> Option<Self>;
+}
+
+// by bits so the score of the last hit compares equal when it comes back
+impl SortKey for f32 {
+    fn to_text(&self) -> String {
+        format!("{:08x}", self.to_bits())
+    }
+
+    fn from_text(text: &str) -> Option<Self> {
+        u32::from_str_radix(text, 16).ok().map(f32::from_bits)
+    }
+}
+
+impl SortKey for i64 {
+    fn to_text(&self) -> String {
+        self.to_string()
+    }
+
+    fn from_text(text: &str) -> Option<Self> {
+        text.parse().ok()
+    }
+}
+
+impl SortKey for String {
+    fn to_text(&self) -> String {
+        self.clone()
+    }
+
+    fn from_text(text: &str) -> Option<Self> {
+        Some(text.to_owned())
+    }
+}
+
+impl<K: SortKey> SortKey for Reverse<K> {
+    fn to_text(&self) -> String {
+        self.0.to_text()
+    }
+
+    fn from_text(text: &str) -> Option<Self> {
+        K::from_text(text).map(Reverse)
+    }
+}
+
+/// Opaque token for the page after the hit with `key` and `url`.
+pub fn encode<K: SortKey>(sort: Sort, key: &K, url: &str) -> String {
+    let text = format!("{:?}\t{}\t{}", sort, url, key.to_text());
+    URL_SAFE_NO_PAD.encode(text)
+}
+
+/// The sort key and url in a token from `encode`, which must have been
+/// made for the same sort order.
+pub fn decode<K: SortKey>(sort: Sort, token: &str) -> Result<(K, String), String> {
+    let invalid = || "Invalid cursor.".to_owned();
+    let bytes = URL_SAFE_NO_PAD.decode(token).map_err(|_| invalid())?;
+    let text = String::from_utf8(bytes).map_err(|_| invalid())?;
+    let mut parts = text.splitn(3, '\t');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(s), Some(url), Some(key)) if s == format!("{:?}", sort) => {
+            let key = K::from_text(key).ok_or_else(invalid)?;
+            Ok((key, url.to_owned()))
+        }
+        (Some(_), Some(_), Some(_)) => Err("Cursor belongs to another sort order.".to_owned()),
+        _ => Err(invalid()),
+    }
+}
+
+#[test]
+fn test_cursor() {
+    let token = encode(Sort::Relevance, &1.25f32, "https://a/1");
+    assert_eq!(
+        decode::<f32>(Sort::Relevance, &token),
+        Ok((1.25, "https://a/1".to_owned()))
+    );
+    let token = encode(Sort::Title, &Reverse("a\tb".to_owned()), "https://a/2");
+    assert_eq!(
+        decode::<Reverse<String>>(Sort::Title, &token),
+        Ok((Reverse("a\tb".to_owned()), "https://a/2".to_owned()))
+    );
+    assert!(decode::<i64>(Sort::Newest, &token).is_err());
+    assert!(decode::<i64>(Sort::Newest, "not a cursor").is_err());
+}
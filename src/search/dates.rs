@@ -0,0 +1,216 @@
+use std::{convert::TryFrom, ops::Bound};
+
+use tantivy::DateTime;
+use time::{util::days_in_year_month, Date, Duration, Month, OffsetDateTime, UtcOffset};
+
+fn invalid(value: &str) -> String {
+    format!(
+        "Invalid date `{}`, expected YYYY, YYYY-MM, YYYY-MM-DD or `last N days|weeks|months|years`.",
+        value
+    )
+}
+
+/// `+08:00`, `-0530`, `Z` or `UTC`.
+pub fn parse_offset(value: &str) -> Result<UtcOffset, String> {
+    let err = || {
+        format!(
+            "Invalid timezone `{}`, expected an offset like +08:00.",
+            value
+        )
+    };
+    if value == "Z" || value.eq_ignore_ascii_case("utc") {
+        return Ok(UtcOffset::UTC);
+    }
+    let (sign, rest) = match value.split_at_checked(1) {
+        Some(("+", rest)) => (1, rest),
+        Some(("-", rest)) => (-1, rest),
+        _ => return Err(err()),
+    };
+    let digits = rest.replace(':', "");
+    if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(err());
+    }
+    let hours: i8 = digits[..2].parse().map_err(|_| err())?;
+    let minutes: i8 = digits[2..].parse().map_err(|_| err())?;
+    UtcOffset::from_hms(sign * hours, sign * minutes, 0).map_err(|_| err())
+}
+
+// the same day `months` earlier, or the last day of that month
+fn months_before(date: Date, months: i32) -> Option<Date> {
+    let index = date.year() * 12 + date.month() as i32 - 1 - months;
+    let (year, month) = (
+        index.div_euclid(12),
+        Month::try_from(index.rem_euclid(12) as u8 + 1).ok()?,
+    );
+    let day = date.day().min(days_in_year_month(year, month));
+    Date::from_calendar_date(year, month, day).ok()
+}
+
+// `last 30 days`, `last-2-weeks` or `last_month`, the calendar days up to
+// and including today
+fn relative_days(value: &str, today: Date) -> Option<(Date, Date)> {
+    let rest = value.strip_prefix("last")?;
+    let rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '-' || c == '_');
+    let digits = rest.bytes().take_while(|b| b.is_ascii_digit()).count();
+    let count: i32 = match digits {
+        0 => 1,
+        _ => rest[..digits].parse().ok().filter(|&n| n > 0)?,
+    };
+    let unit =
+        rest[digits..].trim_start_matches(|c: char| c.is_whitespace() || c == '-' || c == '_');
+    let start = match unit.trim_end_matches('s') {
+        "day" | "d" => today.checked_sub(Duration::days(count as i64)),
+        "week" | "w" => today.checked_sub(Duration::weeks(count as i64)),
+        "month" | "m" => months_before(today, count),
+        "year" | "y" => months_before(today, count.checked_mul(12)?),
+        _ => None,
+    }?;
+    Some((start.next_day()?, today.next_day()?))
+}
+
+/// The first day and the day after the last one of a year, month, day or
+/// recent days.
+fn period(value: &str, today: Date) -> Result<(Date, Date), String> {
+    if let Some(days) = relative_days(value, today) {
+        return Ok(days);
+    }
+    let parts: Vec<&str> = value.split('-').collect();
+    if parts.len() > 3
+        || parts
+            .iter()
+            .any(|p| p.is_empty() || !p.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return Err(invalid(value));
+    }
+    let number = |i: usize| {
+        parts
+            .get(i)
+            .map(|p| p.parse::<u8>().map_err(|_| invalid(value)))
+    };
+    let year: i32 = parts[0].parse().map_err(|_| invalid(value))?;
+    let month = match number(1).transpose()? {
+        Some(m) => Some(Month::try_from(m).map_err(|_| invalid(value))?),
+        None => None,
+    };
+    let day = number(2).transpose()?;
+    let date = |y, m, d| Date::from_calendar_date(y, m, d).map_err(|_| invalid(value));
+    match (month, day) {
+        (None, _) => Ok((
+            date(year, Month::January, 1)?,
+            date(year + 1, Month::January, 1)?,
+        )),
+        (Some(m), None) => {
+            let start = date(year, m, 1)?;
+            Ok((
+                start,
+                months_before(start, -1).ok_or_else(|| invalid(value))?,
+            ))
+        }
+        (Some(m), Some(d)) => {
+            let start = date(year, m, d)?;
+            Ok((start, start.next_day().ok_or_else(|| invalid(value))?))
+        }
+    }
+}
+
+/// Bounds of `2023`, `2023-05`, `2023-05-01`, `last 30 days`, `>2023`,
+/// `<=2023-05`, `2023-01..2023-03` or the open `2023-05..`, whole days in
+/// `offset` with both ends of a range inclusive.
+pub fn parse_range(
+    value: &str,
+    offset: UtcOffset,
+    now: OffsetDateTime,
+) -> Result<(Bound<DateTime>, Bound<DateTime>), String> {
+    let today = now.to_offset(offset).date();
+    let instant = |date: Date| {
+        DateTime::from_timestamp_secs(date.midnight().assume_offset(offset).unix_timestamp())
+    };
+    let span = |v: &str| -> Result<Option<(DateTime, DateTime)>, String> {
+        match v.trim() {
+            "" => Ok(None),
+            v => period(v, today).map(|(start, end)| Some((instant(start), instant(end)))),
+        }
+    };
+    let required = |v: &str| span(v)?.ok_or_else(|| invalid(v));
+    let value = value.trim();
+    let bounds = if let Some(v) = value.strip_prefix(">=") {
+        (Bound::Included(required(v)?.0), Bound::Unbounded)
+    } else if let Some(v) = value.strip_prefix('>') {
+        (Bound::Included(required(v)?.1), Bound::Unbounded)
+    } else if let Some(v) = value.strip_prefix("<=") {
+        (Bound::Unbounded, Bound::Excluded(required(v)?.1))
+    } else if let Some(v) = value.strip_prefix('<') {
+        (Bound::Unbounded, Bound::Excluded(required(v)?.0))
+    } else if let Some((from, to)) = value.split_once("..") {
+        let lower = span(from)?.map_or(Bound::Unbounded, |(start, _)| Bound::Included(start));
+        let upper = span(to)?.map_or(Bound::Unbounded, |(_, end)| Bound::Excluded(end));
+        if let (Bound::Included(start), Bound::Excluded(end)) = (lower, upper) {
+            if start >= end {
+                return Err(format!("Empty date range `{}`.", value));
+            }
+        }
+        (lower, upper)
+    } else {
+        let (start, end) = required(value)?;
+        (Bound::Included(start), Bound::Excluded(end))
+    };
+    Ok(bounds)
+}
+
+#[test]
+fn test_parse_range() {
+    let now = Date::from_calendar_date(2024, Month::March, 31)
+        .unwrap()
+        .with_hms(20, 0, 0)
+        .unwrap()
+        .assume_utc();
+    let range = |v: &str, offset| {
+        parse_range(v, offset, now).map(|(l, u)| {
+            let secs = |b: Bound<DateTime>| match b {
+                Bound::Included(d) | Bound::Excluded(d) => {
+                    let d = OffsetDateTime::from_unix_timestamp(d.into_timestamp_secs()).unwrap();
+                    d.to_offset(offset).date().to_string()
+                }
+                Bound::Unbounded => "*".to_owned(),
+            };
+            (secs(l), secs(u))
+        })
+    };
+    let utc = UtcOffset::UTC;
+    let day = |d: &str| d.to_owned();
+    assert_eq!(
+        range("2023", utc),
+        Ok((day("2023-01-01"), day("2024-01-01")))
+    );
+    assert_eq!(
+        range("2023-12", utc),
+        Ok((day("2023-12-01"), day("2024-01-01")))
+    );
+    assert_eq!(
+        range("2023-05..2023-06-10", utc),
+        Ok((day("2023-05-01"), day("2023-06-11")))
+    );
+    assert_eq!(range("2023-05..", utc), Ok((day("2023-05-01"), day("*"))));
+    assert_eq!(range("<=2023", utc), Ok((day("*"), day("2024-01-01"))));
+    assert_eq!(
+        range("last 30 days", utc),
+        Ok((day("2024-03-02"), day("2024-04-01")))
+    );
+    assert_eq!(
+        range("last-month", utc),
+        Ok((day("2024-03-01"), day("2024-04-01")))
+    );
+    // already April 1st in +08:00
+    let east = parse_offset("+08:00").unwrap();
+    assert_eq!(
+        range("last 1 day", east),
+        Ok((day("2024-04-01"), day("2024-04-02")))
+    );
+    assert!(range("2023-13", utc).is_err());
+    assert!(range("2023-02-30", utc).is_err());
+    assert!(range("yesterday", utc).is_err());
+    assert!(range("last 0 days", utc).is_err());
+    assert!(range("last 200000000 years", utc).is_err());
+    assert!(range("2024..2023", utc).is_err());
+    assert!(parse_offset("8").is_err());
+}
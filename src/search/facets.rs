@@ -0,0 +1,216 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+use tantivy::{
+    aggregation::{
+        agg_req::Aggregations,
+        agg_result::{AggregationResult, BucketResult},
+        AggregationCollector, Key,
+    },
+    collector::{FacetCollector, FacetCounts, FruitHandle, MultiCollector},
+    query::Query,
+    schema::Facet,
+    Searcher,
+};
+
+use crate::migrate::init::facet_text;
+
+static MAX_BUCKETS: u32 = 1000;
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: u64,
+}
+
+/// Matching docs per tag and category including their ancestors, most
+/// frequent first, and per publication year and `YYYY-MM` month, newest
+/// first.
+#[derive(Serialize, Default, Debug)]
+pub struct Facets {
+    pub tags: Vec<FacetCount>,
+    pub category: Vec<FacetCount>,
+    pub year: Vec<FacetCount>,
+    pub month: Vec<FacetCount>,
+}
+
+fn terms_aggregations(fields: &[&str]) -> Aggregations {
+    let mut aggs = serde_json::Map::new();
+    for field in fields {
+        aggs.insert(
+            field.to_string(),
+            serde_json::json!({ "terms": { "field": field, "size": MAX_BUCKETS } }),
+        );
+    }
+    serde_json::from_value(serde_json::Value::Object(aggs)).expect("Invalid aggregations")
+}
+
+fn take_terms(result: Option<AggregationResult>) -> Vec<FacetCount> {
+    let buckets = match result {
+        Some(AggregationResult::BucketResult(BucketResult::Terms { buckets, .. })) => buckets,
+        _ => return vec![],
+    };
+    buckets
+        .into_iter()
+        .filter_map(|bucket| match bucket.key {
+            Key::Str(value) => Some(FacetCount {
+                value,
+                count: bucket.doc_count,
+            }),
+            Key::F64(_) => None,
+        })
+        .collect()
+}
+
+// a collector can only count the children of facets that are not each
+// other's ancestors, so there is one per depth, each counting the children
+// of every facet in the index one level up
+fn facet_collectors(searcher: &Searcher, name: &str) -> Vec<FacetCollector> {
+    let mut depths: Vec<BTreeSet<Facet>> = vec![BTreeSet::new()];
+    let field = searcher.schema().get_field(name).expect("No facet field");
+    for segment in searcher.segment_readers() {
+        let index = segment.inverted_index(field).expect("Cannot read facets");
+        let mut terms = index.terms().stream().expect("Cannot read facets");
+        while terms.advance() {
+            let facet = Facet::from_encoded(terms.key().to_vec()).expect("Invalid facet");
+            let depth = match facet.is_root() {
+                true => 0,
+                false => facet.to_path().len(),
+            };
+            if depths.len() <= depth {
+                depths.resize_with(depth + 1, BTreeSet::new);
+            }
+            depths[depth].insert(facet);
+        }
+    }
+    depths
+        .into_iter()
+        .map(|parents| {
+            let mut collector = FacetCollector::for_field(name);
+            for parent in parents {
+                collector.add_facet(parent);
+            }
+            collector
+        })
+        .collect()
+}
+
+// each doc counts once for a facet however many of its facets are below it,
+// the counts of collector `i` are for facets of depth `i + 1` and their parents
+fn facet_counts(counts: Vec<FacetCounts>) -> Vec<FacetCount> {
+    let mut facets: Vec<FacetCount> = counts
+        .iter()
+        .enumerate()
+        .flat_map(|(i, c)| {
+            c.get(Facet::root())
+                .filter(move |(facet, _)| facet.to_path().len() == i + 1)
+        })
+        .map(|(facet, count)| FacetCount {
+            value: facet_text(facet),
+            count,
+        })
+        .collect();
+    facets.sort_by(|a, b| b.count.cmp(&a.count).then(a.value.cmp(&b.value)));
+    facets
+}
+
+pub fn count(searcher: &Searcher, query: &dyn Query) -> Facets {
+    let mut collector = MultiCollector::new();
+    let months = collector.add_collector(AggregationCollector::from_aggs(
+        terms_aggregations(&["month"]),
+        Default::default(),
+    ));
+    let mut add_facets = |field| -> Vec<FruitHandle<FacetCounts>> {
+        facet_collectors(searcher, field)
+            .into_iter()
+            .map(|c| collector.add_collector(c))
+            .collect()
+    };
+    let (tags, category) = (add_facets("tags_facet"), add_facets("category_facet"));
+    let mut fruits = searcher.search(query, &collector).expect("Search Failed");
+    let mut extract = |handles: Vec<FruitHandle<FacetCounts>>| {
+        let counts = handles
+            .into_iter()
+            .map(|h| h.extract(&mut fruits))
+            .collect();
+        facet_counts(counts)
+    };
+    let (tags, category) = (extract(tags), extract(category));
+    let mut results = months.extract(&mut fruits).0;
+    let mut month = take_terms(results.remove("month"));
+    month.sort_by(|a, b| b.value.cmp(&a.value));
+    let mut years: BTreeMap<&str, u64> = BTreeMap::new();
+    for m in &month {
+        *years
+            .entry(m.value.get(..4).unwrap_or(&m.value))
+            .or_insert(0) += m.count;
+    }
+    let year = years
+        .into_iter()
+        .rev()
+        .map(|(value, count)| FacetCount {
+            value: value.to_owned(),
+            count,
+        })
+        .collect();
+    Facets {
+        tags,
+        category,
+        year,
+        month,
+    }
+}
+
+#[test]
+fn test_count() {
+    use crate::migrate::init::to_facet;
+    use tantivy::{
+        doc,
+        query::AllQuery,
+        schema::{FacetOptions, Schema, FAST, STRING},
+        Index,
+    };
+    let mut builder = Schema::builder();
+    let tags = builder.add_facet_field("tags_facet", FacetOptions::default());
+    let category = builder.add_facet_field("category_facet", FacetOptions::default());
+    let month = builder.add_text_field("month", STRING | FAST);
+    let index = Index::create_in_ram(builder.build());
+    let mut writer = index.writer(15_000_000).unwrap();
+    // sibling tags count once for their parent
+    writer
+        .add_document(doc!(
+            tags => to_facet("lang/rust"),
+            tags => to_facet("lang/go"),
+            category => to_facet("code"),
+            month => "2023-05"
+        ))
+        .unwrap();
+    writer
+        .add_document(doc!(
+            tags => to_facet("lang/rust"),
+            category => to_facet("code/rust"),
+            month => "2023-06"
+        ))
+        .unwrap();
+    writer
+        .add_document(doc!(category => to_facet("life"), month => "2022-01"))
+        .unwrap();
+    writer.commit().unwrap();
+    let facets = count(&index.reader().unwrap().searcher(), &AllQuery);
+    fn pairs(counts: &[FacetCount]) -> Vec<(&str, u64)> {
+        counts.iter().map(|c| (c.value.as_str(), c.count)).collect()
+    }
+    assert_eq!(
+        pairs(&facets.tags),
+        vec![("lang", 2), ("lang/rust", 2), ("lang/go", 1)]
+    );
+    assert_eq!(
+        pairs(&facets.category),
+        vec![("code", 2), ("code/rust", 1), ("life", 1)]
+    );
+    assert_eq!(pairs(&facets.year), vec![("2023", 2), ("2022", 1)]);
+    assert_eq!(
+        pairs(&facets.month),
+        vec![("2023-06", 1), ("2023-05", 1), ("2022-01", 1)]
+    );
+}
@@ -0,0 +1,217 @@
+/// Query language of the keyword box:
+///
+/// ```text
+/// query   := or_expr*                  (all of them must match)
+/// or_expr := unary ("OR" unary)*
+/// unary   := "-" unary | field ":" unary | "(" query ")" | '"' phrase '"' | word
+/// ```
+///
+/// e.g. `"exact phrase" (rust OR go) -java title:tantivy date:>2023-01-01`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Ast {
+    Word(String),
+    Phrase(String),
+    /// the word being typed, see `mark_prefix`
+    Prefix(String),
+    Field(String, Box<Ast>),
+    Not(Box<Ast>),
+    And(Vec<Ast>),
+    Or(Vec<Ast>),
+}
+
+impl Ast {
+    /// Turns the last positive word into a `Prefix` for search-as-you-type.
+    pub fn mark_prefix(&mut self) {
+        match self {
+            Ast::Word(w) => *self = Ast::Prefix(std::mem::take(w)),
+            Ast::Field(_, inner) => inner.mark_prefix(),
+            Ast::And(items) | Ast::Or(items) => {
+                if let Some(last) = items.last_mut() {
+                    last.mark_prefix()
+                }
+            }
+            Ast::Phrase(_) | Ast::Prefix(_) | Ast::Not(_) => (),
+        }
+    }
+
+    /// The query without its excluded parts, `None` when nothing is left.
+    pub fn positive(&self) -> Option<Ast> {
+        match self {
+            Ast::Not(_) => None,
+            Ast::Field(f, inner) => inner.positive().map(|i| Ast::Field(f.clone(), Box::new(i))),
+            Ast::And(items) | Ast::Or(items) => {
+                let mut kept: Vec<Ast> = items.iter().filter_map(|i| i.positive()).collect();
+                match (kept.len(), self) {
+                    (0, _) => None,
+                    (1, _) => kept.pop(),
+                    (_, Ast::And(_)) => Some(Ast::And(kept)),
+                    _ => Some(Ast::Or(kept)),
+                }
+            }
+            _ => Some(self.clone()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    Field(String),
+    Minus,
+    Or,
+    Open,
+    Close,
+}
+
+static FIELDS: &[&str] = &["title", "content", "tag", "tags", "category", "date"];
+
+fn is_field(word: &str) -> bool {
+    FIELDS.contains(&word.to_lowercase().as_str())
+}
+
+fn lex(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | '-' => {
+                chars.next();
+                tokens.push(match c {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    _ => Token::Minus,
+                })
+            }
+            '"' => {
+                chars.next();
+                let phrase: String = chars.by_ref().take_while(|&c| c != '"').collect();
+                tokens.push(Token::Phrase(phrase))
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        break;
+                    }
+                    chars.next();
+                    if c == ':' && is_field(&word) {
+                        word.push(c);
+                        break;
+                    }
+                    word.push(c);
+                }
+                tokens.push(match word.strip_suffix(':') {
+                    Some(field) if is_field(field) => Token::Field(field.to_lowercase()),
+                    _ if word == "OR" => Token::Or,
+                    _ => Token::Word(word),
+                })
+            }
+        }
+    }
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn parse_and(&mut self, nested: bool) -> Result<Ast, String> {
+        let mut items = Vec::new();
+        loop {
+            match self.peek() {
+                None if nested => return Err("Missing closing parenthesis `)`.".to_string()),
+                None => break,
+                Some(Token::Close) if nested => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(Token::Close) => return Err("Unexpected closing parenthesis `)`.".to_string()),
+                _ => items.push(self.parse_or()?),
+            }
+        }
+        Ok(Ast::And(items))
+    }
+
+    fn parse_or(&mut self) -> Result<Ast, String> {
+        let mut items = vec![self.parse_unary()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            items.push(self.parse_unary()?);
+        }
+        Ok(match items.len() {
+            1 => items.remove(0),
+            _ => Ast::Or(items),
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<Ast, String> {
+        self.pos += 1;
+        match self.tokens.get(self.pos - 1) {
+            Some(Token::Minus) => Ok(Ast::Not(Box::new(self.parse_unary()?))),
+            Some(Token::Field(f)) => {
+                let field = f.clone();
+                Ok(Ast::Field(field, Box::new(self.parse_unary()?)))
+            }
+            Some(Token::Open) => self.parse_and(true),
+            Some(Token::Word(w)) => Ok(Ast::Word(w.clone())),
+            Some(Token::Phrase(p)) => Ok(Ast::Phrase(p.clone())),
+            Some(Token::Or) | Some(Token::Close) | None => {
+                Err("Incomplete query, `-`, `OR` and `field:` need a term after them.".to_string())
+            }
+        }
+    }
+}
+
+pub fn parse(text: &str) -> Result<Ast, String> {
+    Parser {
+        tokens: lex(text),
+        pos: 0,
+    }
+    .parse_and(false)
+}
+
+#[test]
+fn test_parse_query() {
+    let word = |w: &str| Ast::Word(w.to_owned());
+    assert_eq!(
+        parse("\"exact phrase\" (rust OR go) -java title:tantivy date:>2023-01-01").unwrap(),
+        Ast::And(vec![
+            Ast::Phrase("exact phrase".to_owned()),
+            Ast::And(vec![Ast::Or(vec![word("rust"), word("go")])]),
+            Ast::Not(Box::new(word("java"))),
+            Ast::Field("title".to_owned(), Box::new(word("tantivy"))),
+            Ast::Field("date".to_owned(), Box::new(word(">2023-01-01"))),
+        ])
+    );
+    assert_eq!(
+        parse("a OR b c").unwrap(),
+        Ast::And(vec![Ast::Or(vec![word("a"), word("b")]), word("c")])
+    );
+    assert_eq!(
+        parse("http://x 机器学习").unwrap(),
+        Ast::And(vec![word("http://x"), word("机器学习")])
+    );
+    let mut ast = parse("rust tanti").unwrap();
+    ast.mark_prefix();
+    assert_eq!(
+        ast,
+        Ast::And(vec![word("rust"), Ast::Prefix("tanti".to_owned())])
+    );
+    assert_eq!(
+        parse("rust -go (c OR -d)").unwrap().positive(),
+        Some(Ast::And(vec![word("rust"), word("c")]))
+    );
+    assert_eq!(parse("-go").unwrap().positive(), None);
+    assert!(parse("(rust").is_err());
+    assert!(parse("rust OR").is_err());
+}
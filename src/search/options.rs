@@ -0,0 +1,227 @@
+use time::UtcOffset;
+
+use super::dates;
+
+#[derive(Default, Debug, PartialEq)]
+pub enum RequestType {
+    #[default]
+    Search,
+    /// completions of the keywords from titles, tags and categories
+    Suggest,
+    /// posts similar to the one whose url is the keyword
+    Related,
+    /// posts per year and month, or the posts of the `YYYY-MM` keyword
+    Archive,
+    /// the post whose url is the keyword
+    Get,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Sort {
+    Relevance,
+    Newest,
+    Oldest,
+    /// alphabetical by title
+    Title,
+}
+
+static HIT_FIELDS: &[&str] = &[
+    "url", "date", "title", "snippet", "category", "tags", "content",
+];
+static MAX_SNIPPET_LENGTH: usize = 1000;
+static MAX_FRAGMENTS: usize = 5;
+
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+pub enum SnippetFormat {
+    /// escaped html with the highlight tags inline
+    #[default]
+    Html,
+    /// plain text with the char ranges of the highlights beside it
+    Text,
+}
+
+/// How the highlighted snippets of a hit are cut and marked.
+#[derive(Debug, PartialEq)]
+pub struct SnippetOptions {
+    /// most UTF-8 bytes per fragment, so a third as many CJK chars
+    pub length: usize,
+    /// most fragments of the content, joined by an ellipsis
+    pub fragments: usize,
+    /// html around each highlighted word
+    pub highlight: (String, String),
+    pub format: SnippetFormat,
+}
+
+impl Default for SnippetOptions {
+    fn default() -> SnippetOptions {
+        SnippetOptions {
+            length: 300,
+            fragments: 1,
+            highlight: ("<b>".to_owned(), "</b>".to_owned()),
+            format: SnippetFormat::Html,
+        }
+    }
+}
+
+/// Per-request switches, sent as `key:value` strings after the keywords.
+#[derive(Default, Debug, PartialEq)]
+pub struct SearchOptions {
+    pub request: RequestType,
+    /// relevance with keywords and newest first without, by default
+    pub sort: Option<Sort>,
+    /// tolerate one typo in longer ASCII words
+    pub fuzzy: bool,
+    /// count the matching docs per tag, category, year and month
+    pub facets: bool,
+    /// match the last word as a prefix, for search-as-you-type
+    pub prefix: bool,
+    /// continue after the last hit of a previous page, replaces the page number
+    pub cursor: Option<String>,
+    /// the hit fields to return, all but the full `content` when `None`
+    pub fields: Option<Vec<String>>,
+    pub snippet: SnippetOptions,
+    /// add the score and the score explanation of each hit
+    pub debug: bool,
+    /// posts in a date range, see `dates::parse_range`
+    pub date: Option<String>,
+    /// the offset dates are in, UTC by default
+    pub tz: Option<UtcOffset>,
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "1" | "on" => Ok(true),
+        "false" | "0" | "off" => Ok(false),
+        _ => Err(format!("Option `{}` expects true or false.", key)),
+    }
+}
+
+fn parse_count(key: &str, value: &str, max: usize) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n.min(max)),
+        _ => Err(format!("Option `{}` expects a positive number.", key)),
+    }
+}
+
+// `mark` is `<mark>`, `mark.hl` is `<mark class="hl">`
+fn parse_highlight(value: &str) -> Result<(String, String), String> {
+    let (tag, class) = value.split_once('.').unwrap_or((value, ""));
+    let is_name = |s: &str| {
+        s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+    if tag.is_empty() || !tag.chars().all(|c| c.is_ascii_alphanumeric()) || !is_name(class) {
+        return Err(format!("Invalid highlight tag `{}`.", value));
+    }
+    let open = match class {
+        "" => format!("<{}>", tag),
+        _ => format!("<{} class=\"{}\">", tag, class),
+    };
+    Ok((open, format!("</{}>", tag)))
+}
+
+impl SearchOptions {
+    /// Whether the hits should include `field`.
+    pub fn wants(&self, field: &str) -> bool {
+        match &self.fields {
+            Some(fields) => fields.iter().any(|f| f == field),
+            None => field != "content",
+        }
+    }
+
+    pub fn parse(options: &[String]) -> Result<SearchOptions, String> {
+        let mut opts = SearchOptions::default();
+        for option in options.iter().filter(|o| !o.is_empty()) {
+            let (key, value) = option.split_once(':').unwrap_or((option, "true"));
+            match key {
+                "type" => {
+                    opts.request = match value {
+                        "search" => RequestType::Search,
+                        "suggest" => RequestType::Suggest,
+                        "related" => RequestType::Related,
+                        "archive" => RequestType::Archive,
+                        "get" => RequestType::Get,
+                        _ => return Err(format!("Unknown request type `{}`.", value)),
+                    }
+                }
+                "sort" => {
+                    opts.sort = Some(match value {
+                        "relevance" => Sort::Relevance,
+                        "newest" | "date_desc" => Sort::Newest,
+                        "oldest" | "date_asc" => Sort::Oldest,
+                        "title" => Sort::Title,
+                        _ => return Err(format!("Unknown sort order `{}`.", value)),
+                    })
+                }
+                "fuzzy" => opts.fuzzy = parse_bool(key, value)?,
+                "facets" => opts.facets = parse_bool(key, value)?,
+                "prefix" => opts.prefix = parse_bool(key, value)?,
+                "debug" => opts.debug = parse_bool(key, value)?,
+                "date" => opts.date = Some(value.to_owned()),
+                "tz" => opts.tz = Some(dates::parse_offset(value)?),
+                "cursor" => opts.cursor = Some(value.to_owned()),
+                "fields" => {
+                    let fields: Vec<String> = value.split(',').map(|f| f.to_owned()).collect();
+                    if let Some(f) = fields.iter().find(|f| !HIT_FIELDS.contains(&f.as_str())) {
+                        return Err(format!("Unknown field `{}`.", f));
+                    }
+                    opts.fields = Some(fields);
+                }
+                "snippet_length" => {
+                    opts.snippet.length = parse_count(key, value, MAX_SNIPPET_LENGTH)?
+                }
+                "fragments" => opts.snippet.fragments = parse_count(key, value, MAX_FRAGMENTS)?,
+                "highlight" => opts.snippet.highlight = parse_highlight(value)?,
+                "format" => {
+                    opts.snippet.format = match value {
+                        "html" => SnippetFormat::Html,
+                        "text" => SnippetFormat::Text,
+                        _ => return Err(format!("Unknown snippet format `{}`.", value)),
+                    }
+                }
+                _ => return Err(format!("Unknown option `{}`.", key)),
+            }
+        }
+        Ok(opts)
+    }
+}
+
+#[test]
+fn test_parse_options() {
+    let parse =
+        |o: &[&str]| SearchOptions::parse(&o.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+    assert_eq!(parse(&[]).unwrap(), SearchOptions::default());
+    assert!(parse(&["fuzzy"]).unwrap().fuzzy);
+    assert!(!parse(&["fuzzy:false"]).unwrap().fuzzy);
+    assert!(parse(&["debug:on"]).unwrap().debug);
+    assert!(parse(&["fuzzy:maybe"]).is_err());
+    assert_eq!(
+        parse(&["type:suggest"]).unwrap().request,
+        RequestType::Suggest
+    );
+    assert!(parse(&["type:other"]).is_err());
+    assert_eq!(parse(&["sort:oldest"]).unwrap().sort, Some(Sort::Oldest));
+    assert_eq!(
+        parse(&["cursor:abc"]).unwrap().cursor,
+        Some("abc".to_owned())
+    );
+    let opts = parse(&["fields:url,title", "fragments:9", "highlight:mark.hl"]).unwrap();
+    assert!(opts.wants("title") && !opts.wants("snippet"));
+    assert!(!parse(&[]).unwrap().wants("content"));
+    assert_eq!(opts.snippet.fragments, MAX_FRAGMENTS);
+    assert_eq!(opts.snippet.highlight.0, "<mark class=\"hl\">");
+    assert_eq!(
+        parse(&["format:text"]).unwrap().snippet.format,
+        SnippetFormat::Text
+    );
+    assert!(parse(&["format:xml"]).is_err());
+    assert_eq!(
+        parse(&["tz:+08:00"]).unwrap().tz,
+        UtcOffset::from_hms(8, 0, 0).ok()
+    );
+    assert!(parse(&["tz:Asia"]).is_err());
+    assert!(parse(&["fields:url,body"]).is_err());
+    assert!(parse(&["snippet_length:0"]).is_err());
+    assert!(parse(&["highlight:b onclick=x"]).is_err());
+    assert!(parse(&["color:red"]).is_err());
+}
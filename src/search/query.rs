@@ -1,123 +1,524 @@
 use std::ops::Bound;
 use tantivy::{
-    collector::{Collector, TopDocs},
-    query::{BooleanQuery, Occur, PhraseQuery, Query, QueryParser, RangeQuery, TermQuery},
+    collector::{Count, TopDocs},
+    columnar::StrColumn,
+    query::{
+        AllQuery, BooleanQuery, BoostQuery, Explanation, FuzzyTermQuery, MoreLikeThisQuery, Occur,
+        PhrasePrefixQuery, PhraseQuery, Query, RangeQuery, TermQuery,
+    },
     schema::{Field, IndexRecordOption, Schema, Term, Value},
-    DateTime, DocAddress, Document, Index, IndexReader, SnippetGenerator,
+    tokenizer::TextAnalyzer,
+    DateTime, DocAddress, DocId, Document, Index, IndexReader, Score, SegmentReader,
+    SnippetGenerator,
 };
 
-use std::vec;
+use std::{
+    cmp::{Ordering, Reverse},
+    sync::Arc,
+    vec,
+};
+use time::{OffsetDateTime, UtcOffset};
 
-use crate::tokenizer::{segmentation::cut_string, UTF8Tokenizer};
+use super::{
+    archive::{self, YearCount},
+    cursor::{self, SortKey},
+    dates,
+    grammar::{self, Ast},
+    options::{SearchOptions, SnippetOptions, Sort},
+    snippet::{self, Highlighted},
+    spelling,
+    suggest::{Suggester, Suggestion},
+    synonyms::Synonyms,
+};
+use crate::{
+    config::read::{Boost, Paging, Search},
+    migrate::init::{register_tokenizers, to_facet},
+    tokenizer::{dictionary::is_han, pinyin::split_syllables},
+};
 
 #[derive(Clone)]
 pub struct Fields {
     pub url: Field,
     pub date: Field,
-    tags: Field,
+    pub tags: Field,
     pub title: Field,
     pub content: Field,
     pub category: Field,
+    pinyin: Field,
+    tags_facet: Field,
+    category_facet: Field,
 }
 
 // static DELIMITER: &str = ",";
 
+/// One page of hits, the count of all matching docs and, when the page is
+/// full, the cursor for the next one.
+pub struct Page {
+    pub docs: Vec<DocAddress>,
+    pub count: usize,
+    pub cursor: Option<String>,
+}
+
+/// The url of a doc as a sort tie-break, only read from the fast column when
+/// the sort keys of two docs are equal.
+#[derive(Clone)]
+struct UrlKey {
+    urls: Arc<Option<StrColumn>>,
+    doc: DocId,
+}
+
+impl UrlKey {
+    fn text(&self) -> String {
+        QuerySchema::first_str(&self.urls, self.doc)
+    }
+}
+
+impl PartialEq for UrlKey {
+    fn eq(&self, other: &UrlKey) -> bool {
+        self.text() == other.text()
+    }
+}
+
+impl PartialOrd for UrlKey {
+    fn partial_cmp(&self, other: &UrlKey) -> Option<Ordering> {
+        self.text().partial_cmp(&other.text())
+    }
+}
+
 #[derive(Clone)]
 pub struct QuerySchema {
     pub fields: Fields,
     pub schema: Schema,
-    pub query_parser: QueryParser,
     pub reader: IndexReader,
+    analyzer: TextAnalyzer,
+    synonyms: Arc<Synonyms>,
+    suggester: Arc<Suggester>,
+    boost: Boost,
+    paging: Paging,
 }
 
-impl QuerySchema {
-    pub fn tokenizer() -> UTF8Tokenizer {
-        UTF8Tokenizer {}
+static FUZZY_MIN_LENGTH: usize = 5;
+static CJK_PUNCTUATION: &[&str] = &[
+    "，", "。", "、", "：", "；", "！", "？", "“", "”", "‘", "’", "（", "）", "《", "》", "…",
+];
+
+/// `1 + recency` for a new post and `1 + recency / 2` for one
+/// `recency_half_life` days old.
+fn recency_factor(boost: &Boost, now: i64, date: DateTime) -> f32 {
+    let days = (now - date.into_timestamp_secs()).max(0) as f32 / 86400.0;
+    1.0 + boost.recency * 0.5f32.powf(days / boost.recency_half_life)
+}
+
+// one query when there is a single alternative, `None` when there is none
+fn any_of(mut querys: Vec<Box<dyn Query>>) -> Option<Box<dyn Query>> {
+    match querys.len() {
+        0 | 1 => querys.pop(),
+        _ => Some(Box::new(BooleanQuery::new(
+            querys.into_iter().map(|q| (Occur::Should, q)).collect(),
+        ))),
     }
+}
+
+impl QuerySchema {
+    /// Filters like `tags:rust` (all of), `tags:rust|go` (any of) and
+    /// `-category:life` (none of).
     pub fn make_terms_query(&self, terms: Vec<String>, box_qs: &mut Vec<Box<dyn Query>>) {
         let mut q_vecs: Vec<(Occur, Box<dyn Query>)> = Vec::new();
         for term in terms {
-            let p = term.splitn(2, ":").collect::<Vec<&str>>();
-            let field = match p[0] {
-                "tags" => self.fields.tags,
+            let (occur, term) = match term.strip_prefix('-') {
+                Some(t) => (Occur::MustNot, t),
+                None => (Occur::Must, term.as_str()),
+            };
+            let (name, values) = match term.split_once(':') {
+                Some(p) => p,
+                None => continue,
+            };
+            let field = match name {
+                "tags" | "tag" => self.fields.tags,
                 "category" => self.fields.category,
                 _ => continue,
             };
-            q_vecs.push((
-                Occur::Must,
-                Box::new(TermQuery::new(
-                    Term::from_field_text(field, &p[1].to_lowercase()),
-                    IndexRecordOption::Basic,
-                )),
-            ))
+            let values: Vec<Box<dyn Query>> = values
+                .split('|')
+                .filter(|v| !v.is_empty())
+                .map(|v| self.make_exact_term(field, v))
+                .collect();
+            if let Some(q) = any_of(values) {
+                q_vecs.push((occur, q))
+            }
         }
-        if q_vecs.len() != 0 {
-            box_qs.push(Box::new(BooleanQuery::new(q_vecs)))
+        if q_vecs.iter().all(|(occur, _)| *occur == Occur::MustNot) {
+            if q_vecs.is_empty() {
+                return;
+            }
+            q_vecs.push((Occur::Must, Box::new(AllQuery)));
         }
+        box_qs.push(Box::new(BooleanQuery::new(q_vecs)))
     }
 
-    fn make_field_search(&self, word: &str, op: Occur) -> Box<dyn Query> {
-        let chs = cut_string(word);
-        let title: Box<dyn Query>;
-        let content: Box<dyn Query>;
-        if chs.len() == 1 {
-            title = Box::new(TermQuery::new(
-                Term::from_field_text(self.fields.title, word),
-                IndexRecordOption::WithFreqsAndPositions,
-            ));
-            content = Box::new(TermQuery::new(
-                Term::from_field_text(self.fields.content, word),
-                IndexRecordOption::WithFreqsAndPositions,
-            ));
-        } else {
-            let mut title_terms: Vec<(usize, Term)> = Vec::with_capacity(chs.len());
-            let mut cnt_terms: Vec<(usize, Term)> = Vec::with_capacity(chs.len());
-            let mut offset = 0;
-            for ch in chs {
-                title_terms.push((offset, Term::from_field_text(self.fields.title, ch)));
-                cnt_terms.push((offset, Term::from_field_text(self.fields.content, ch)));
-                offset += ch.len();
+    fn tokenize(&self, word: &str) -> Vec<(usize, String)> {
+        let mut analyzer = self.analyzer.clone();
+        let mut stream = analyzer.token_stream(word);
+        let mut tokens: Vec<(usize, usize, usize, String)> = Vec::new();
+        while let Some(token) = stream.next() {
+            // the words inside a long one follow it and are only there to be
+            // indexed
+            let inside = |&(from, to, _, _): &(usize, usize, usize, String)| {
+                from <= token.offset_from && token.offset_to <= to
+            };
+            if tokens.last().is_some_and(inside) {
+                continue;
             }
-            title = Box::new(PhraseQuery::new_with_offset(title_terms));
-            content = Box::new(PhraseQuery::new_with_offset(cnt_terms));
+            tokens.push((
+                token.offset_from,
+                token.offset_to,
+                token.position,
+                token.text.clone(),
+            ));
         }
-        Box::new(BooleanQuery::new(vec![(op, content), (op, title)]))
+        tokens
+            .into_iter()
+            .map(|(_, _, pos, text)| (pos, text))
+            .collect()
     }
 
-    pub fn make_keyword_query(&self, keyword: Vec<String>) -> Result<Vec<Box<dyn Query>>, &str> {
-        let (mut must, mut mustnot) = (Vec::new(), Vec::new());
-        for key in keyword {
-            if key.starts_with("-") {
-                let t = key[1..].to_string();
-                mustnot.push(t)
-            } else if key != "" {
-                must.push(key)
+    // `None` when every token of the word is dropped, e.g. a stop word;
+    // with `prefix` the last token may be incomplete unless it is Han
+    fn make_field_search(
+        &self,
+        word: &str,
+        fields: &[Field],
+        opts: &SearchOptions,
+        prefix: bool,
+    ) -> Option<Box<dyn Query>> {
+        let chs = self.tokenize(word);
+        let prefix = prefix && chs.last().is_some_and(|(_, ch)| !ch.chars().any(is_han));
+        if chs.is_empty() {
+            return None;
+        }
+        let querys = fields
+            .iter()
+            .map(|&field| {
+                let q: Box<dyn Query> = if prefix {
+                    Box::new(PhrasePrefixQuery::new_with_offset(
+                        chs.iter()
+                            .map(|(offset, ch)| (*offset, Term::from_field_text(field, ch)))
+                            .collect(),
+                    ))
+                } else if chs.len() == 1 {
+                    self.make_term_search(field, &chs[0].1, opts)
+                } else {
+                    Box::new(PhraseQuery::new_with_offset(
+                        chs.iter()
+                            .map(|(offset, ch)| (*offset, Term::from_field_text(field, ch)))
+                            .collect(),
+                    ))
+                };
+                (Occur::Should, self.make_boost(field, q))
+            })
+            .collect();
+        Some(Box::new(BooleanQuery::new(querys)))
+    }
+
+    // the `[search.boost]` weight of a field
+    fn make_boost(&self, field: Field, query: Box<dyn Query>) -> Box<dyn Query> {
+        let boost = match field {
+            f if f == self.fields.title => self.boost.title,
+            f if f == self.fields.tags => self.boost.tags,
+            f if f == self.fields.category => self.boost.category,
+            f if f == self.fields.content => self.boost.content,
+            _ => 1.0,
+        };
+        if boost == 1.0 {
+            return query;
+        }
+        Box::new(BoostQuery::new(query, boost))
+    }
+
+    /// Exact term, or with `fuzzy` on, the exact term boosted above its
+    /// one-typo neighbours for ASCII words of `FUZZY_MIN_LENGTH` or more.
+    fn make_term_search(&self, field: Field, word: &str, opts: &SearchOptions) -> Box<dyn Query> {
+        let term = Term::from_field_text(field, word);
+        let exact = Box::new(TermQuery::new(
+            term.clone(),
+            IndexRecordOption::WithFreqsAndPositions,
+        ));
+        if !opts.fuzzy
+            || word.len() < FUZZY_MIN_LENGTH
+            || !word.bytes().all(|b| b.is_ascii_alphabetic())
+        {
+            return exact;
+        }
+        Box::new(BooleanQuery::new(vec![
+            (Occur::Should, Box::new(BoostQuery::new(exact, 2.0))),
+            (Occur::Should, Box::new(FuzzyTermQuery::new(term, 1, true))),
+        ]))
+    }
+
+    // a keyword matches any of its synonyms
+    fn make_synonym_search(
+        &self,
+        word: &str,
+        fields: &[Field],
+        opts: &SearchOptions,
+    ) -> Option<Box<dyn Query>> {
+        let querys: Vec<Box<dyn Query>> = self
+            .synonyms
+            .expand(word)
+            .iter()
+            .filter_map(|w| self.make_field_search(w, fields, opts, false))
+            .collect();
+        any_of(querys)
+    }
+
+    // a tag or category, or any of their descendants
+    fn make_exact_term(&self, field: Field, value: &str) -> Box<dyn Query> {
+        let facet_field = match field {
+            f if f == self.fields.tags => self.fields.tags_facet,
+            _ => self.fields.category_facet,
+        };
+        Box::new(TermQuery::new(
+            Term::from_facet(facet_field, &to_facet(value)),
+            IndexRecordOption::Basic,
+        ))
+    }
+
+    /// `date:2023`, `date:>2023-01-01`, `date:"last 30 days"` or
+    /// `date:2023-01..2023-02`, see `dates::parse_range`.
+    pub fn make_date_range(
+        &self,
+        value: &str,
+        opts: &SearchOptions,
+    ) -> Result<Box<dyn Query>, String> {
+        let offset = opts.tz.unwrap_or(UtcOffset::UTC);
+        let (lower, upper) = dates::parse_range(value, offset, OffsetDateTime::now_utc())?;
+        Ok(Box::new(RangeQuery::new_date_bounds(
+            "date".to_string(),
+            lower,
+            upper,
+        )))
+    }
+
+    fn compile_term(
+        &self,
+        ast: &Ast,
+        field: Option<&str>,
+        opts: &SearchOptions,
+    ) -> Result<Option<Box<dyn Query>>, String> {
+        let (text, synonyms, prefix) = match ast {
+            Ast::Word(w) => (w.as_str(), true, false),
+            Ast::Phrase(p) => (p.as_str(), false, false),
+            Ast::Prefix(p) => (p.as_str(), false, true),
+            _ => unreachable!(),
+        };
+        let fields: &[Field] = match field {
+            None => &[self.fields.content, self.fields.title],
+            Some("title") => &[self.fields.title],
+            Some("content") => &[self.fields.content],
+            Some("tag") | Some("tags") => {
+                return Ok(Some(self.make_exact_term(self.fields.tags, text)))
             }
+            Some("category") => return Ok(Some(self.make_exact_term(self.fields.category, text))),
+            Some(_) => return self.make_date_range(text, opts).map(Some),
+        };
+        let text = text.to_lowercase();
+        let text_query = if synonyms {
+            self.make_synonym_search(&text, fields, opts)
+        } else {
+            self.make_field_search(&text, fields, opts, prefix)
+        };
+        if field.is_some() || text_query.is_none() {
+            return Ok(text_query);
+        }
+        // without a field prefix a keyword may also be a tag or category
+        let mut querys: Vec<Box<dyn Query>> = text_query.into_iter().collect();
+        for field in [self.fields.tags, self.fields.category].iter() {
+            querys.push(self.make_boost(*field, self.make_exact_term(*field, &text)));
         }
-        if must.len() == 0 {
-            if mustnot.len() != 0 {
-                return Err("It is forbidden queries that are only `excluding`.");
+        Ok(any_of(querys))
+    }
+
+    fn compile(
+        &self,
+        ast: &Ast,
+        field: Option<&str>,
+        opts: &SearchOptions,
+    ) -> Result<Option<Box<dyn Query>>, String> {
+        match ast {
+            Ast::Word(_) | Ast::Phrase(_) | Ast::Prefix(_) => self.compile_term(ast, field, opts),
+            Ast::Field(f, inner) => self.compile(inner, Some(f), opts),
+            Ast::Or(items) => {
+                let mut querys = Vec::new();
+                for item in items {
+                    querys.extend(self.compile(item, field, opts)?);
+                }
+                Ok(any_of(querys))
+            }
+            Ast::Not(inner) => Ok(self.compile(inner, field, opts)?.map(|q| {
+                Box::new(BooleanQuery::new(vec![
+                    (Occur::Must, Box::new(AllQuery) as Box<dyn Query>),
+                    (Occur::MustNot, q),
+                ])) as Box<dyn Query>
+            })),
+            Ast::And(items) => {
+                let (mut must, mut must_not) = (Vec::new(), Vec::new());
+                for item in items {
+                    match item {
+                        Ast::Not(inner) => must_not.extend(self.compile(inner, field, opts)?),
+                        _ => must.extend(self.compile(item, field, opts)?),
+                    }
+                }
+                if must.is_empty() {
+                    if must_not.is_empty() {
+                        return Ok(None);
+                    }
+                    // the filters of the request narrow down what is left
+                    must.push(Box::new(AllQuery));
+                }
+                if must.len() == 1 && must_not.is_empty() {
+                    return Ok(must.pop());
+                }
+                let querys = must
+                    .into_iter()
+                    .map(|q| (Occur::Must, q))
+                    .chain(must_not.into_iter().map(|q| (Occur::MustNot, q)))
+                    .collect();
+                Ok(Some(Box::new(BooleanQuery::new(querys))))
             }
-            return Ok(vec![]);
         }
-        let mut querys: Vec<(Occur, Box<dyn Query>)> = Vec::new();
-        let mut must_not: Vec<(Occur, Box<dyn Query>)> = Vec::new();
-        for word in must {
-            querys.push((
-                Occur::Must,
-                self.make_field_search(&word.to_lowercase(), Occur::Should),
-            ))
+    }
+
+    pub fn make_keyword_query(
+        &self,
+        keyword: Vec<String>,
+        opts: &SearchOptions,
+    ) -> Result<Vec<Box<dyn Query>>, String> {
+        let mut ast = grammar::parse(&keyword.join(" "))?;
+        if opts.prefix {
+            ast.mark_prefix();
+        }
+        Ok(self.compile(&ast, None, opts)?.into_iter().collect())
+    }
+
+    /// The keywords without the excluded ones, for picking and highlighting
+    /// snippets.
+    pub fn make_highlight_query(
+        &self,
+        keyword: &[String],
+        opts: &SearchOptions,
+    ) -> Option<Box<dyn Query>> {
+        let mut ast = grammar::parse(&keyword.join(" ")).ok()?;
+        if opts.prefix {
+            ast.mark_prefix();
         }
-        for word in mustnot {
-            must_not.push((
-                Occur::Must,
-                self.make_field_search(&word.to_lowercase(), Occur::Should),
+        self.compile(&ast.positive()?, None, opts).ok().flatten()
+    }
+
+    fn make_phrase(&self, field: Field, words: Vec<&str>) -> Box<dyn Query> {
+        let terms: Vec<Term> = words
+            .into_iter()
+            .map(|w| Term::from_field_text(field, w))
+            .collect();
+        if terms.len() == 1 {
+            Box::new(TermQuery::new(
+                terms[0].clone(),
+                IndexRecordOption::WithFreqsAndPositions,
             ))
+        } else {
+            Box::new(PhraseQuery::new(terms))
         }
-        if must_not.len() != 0 {
-            querys.push((Occur::MustNot, Box::new(BooleanQuery::new(must_not))));
+    }
+
+    /// Replaces the keyword query with a pinyin one when lowercase ASCII
+    /// keywords hit nothing, so "jqxx" and "jiqixuexi" still find 机器学习.
+    pub fn pinyin_fallback(&self, keyword: &[String], box_qs: &mut [Box<dyn Query>]) {
+        let is_pinyin = |k: &String| !k.is_empty() && k.bytes().all(|b| b.is_ascii_lowercase());
+        if box_qs.is_empty() || !keyword.iter().all(is_pinyin) {
+            return;
         }
-        Ok(vec![Box::new(BooleanQuery::new(querys))])
+        let searcher = self.reader.searcher();
+        if searcher.search(&box_qs[0], &Count).unwrap_or(0) > 0 {
+            return;
+        }
+        let mut querys: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        for key in keyword {
+            let initials = key.split("").filter(|s| !s.is_empty()).collect();
+            let mut alternatives = vec![(
+                Occur::Should,
+                self.make_phrase(self.fields.pinyin, initials),
+            )];
+            if let Some(syllables) = split_syllables(key) {
+                alternatives.push((
+                    Occur::Should,
+                    self.make_phrase(self.fields.pinyin, syllables),
+                ));
+            }
+            querys.push((Occur::Must, Box::new(BooleanQuery::new(alternatives))));
+        }
+        box_qs[0] = Box::new(BooleanQuery::new(querys));
+    }
+
+    fn make_url_query(&self, url: &str) -> TermQuery {
+        let url_term = Term::from_field_text(self.fields.url, url);
+        TermQuery::new(url_term, IndexRecordOption::Basic)
+    }
+
+    /// The stored post whose url is `url`.
+    pub fn find_url(&self, url: &str) -> Option<Document> {
+        let searcher = self.reader.searcher();
+        let (_, doc_addr) = searcher
+            .search(&self.make_url_query(url), &TopDocs::with_limit(1))
+            .ok()?
+            .pop()?;
+        searcher.doc(doc_addr).ok()
+    }
+
+    /// Posts sharing the most distinctive title, content and tag terms with
+    /// the post at `url`, which itself is left out. `None` for an unknown url.
+    pub fn make_related_query(&self, url: &str) -> Option<BooleanQuery> {
+        let url_query = self.make_url_query(url);
+        let doc = self.find_url(url)?;
+        let values = [self.fields.title, self.fields.content, self.fields.tags]
+            .iter()
+            .map(|&field| (field, doc.get_all(field).cloned().collect()))
+            .collect();
+        let mlt = MoreLikeThisQuery::builder()
+            .with_min_doc_frequency(2)
+            .with_min_term_frequency(1)
+            .with_max_query_terms(25)
+            // spaces and ASCII punctuation are tokens of their own too
+            .with_min_word_length(2)
+            .with_stop_words(CJK_PUNCTUATION.iter().map(|p| p.to_string()).collect())
+            .with_document_fields(values);
+        Some(BooleanQuery::new(vec![
+            (Occur::Must, Box::new(mlt)),
+            (Occur::MustNot, Box::new(url_query)),
+        ]))
+    }
+
+    /// The keywords with every unknown ASCII word replaced by its closest
+    /// indexed term, `None` when nothing needs correcting.
+    pub fn did_you_mean(&self, keyword: &[String]) -> Option<String> {
+        let searcher = self.reader.searcher();
+        let fields = [self.fields.content, self.fields.title];
+        let mut corrected = false;
+        let words: Vec<String> = keyword
+            .iter()
+            .map(|word| {
+                let tokens = self.tokenize(word);
+                if tokens.len() != 1 || !word.bytes().all(|b| b.is_ascii_alphabetic()) {
+                    return word.clone();
+                }
+                match spelling::correct(&searcher, &fields, &tokens[0].1) {
+                    Some(term) => {
+                        corrected = true;
+                        term
+                    }
+                    None => word.clone(),
+                }
+            })
+            .collect();
+        corrected.then(|| words.join(" "))
     }
 
     fn transform_date_bound(&self, timestamp: i64) -> Bound<DateTime> {
@@ -128,67 +529,243 @@ impl QuerySchema {
         Bound::Included(d)
     }
 
-    pub fn make_date_query(&self, dates: Vec<i64>, box_qs: &mut Vec<Box<dyn Query>>) {
-        if dates.len() == 0 {
-            return;
+    /// `[start, end]` unix timestamps from the protocol, 0 for an open end.
+    pub fn make_date_query(
+        &self,
+        dates: Vec<i64>,
+        box_qs: &mut Vec<Box<dyn Query>>,
+    ) -> Result<(), String> {
+        match dates[..] {
+            [] => Ok(()),
+            [start, end] if start != 0 && end != 0 && start > end => {
+                Err("The date range starts after it ends.".to_string())
+            }
+            [start, end] => {
+                box_qs.push(Box::new(RangeQuery::new_date_bounds(
+                    "date".to_string(),
+                    self.transform_date_bound(start),
+                    self.transform_date_bound(end),
+                )));
+                Ok(())
+            }
+            _ => Err("The date range expects a start and an end.".to_string()),
         }
-        box_qs.push(Box::new(RangeQuery::new_date_bounds(
-            "date".to_string(),
-            self.transform_date_bound(dates[0]),
-            self.transform_date_bound(dates[1]),
-        )))
     }
+
     pub fn make_snippet_gen(
         &self,
         keyword_query: &Box<dyn Query>,
         field: Field,
+        max_bytes: usize,
     ) -> Option<SnippetGenerator> {
         let mut spg =
             SnippetGenerator::create(&self.reader.searcher(), keyword_query, field).unwrap();
-        spg.set_max_num_chars(300);
+        spg.set_max_num_chars(max_bytes);
         Some(spg)
     }
 
+    /// The highlighted fragments of `field_value` joined by an ellipsis, or
+    /// its lead sentences without any.
     pub fn make_snippet_value(
         &self,
         sp_gen: &Option<SnippetGenerator>,
-        doc: &Document,
         field_value: &Value,
-    ) -> String {
-        let value_str = field_value
-            .as_text()
-            .unwrap()
-            .chars()
-            .take(140)
-            .skip(0)
-            .collect();
-        match sp_gen {
-            Some(spg) => {
-                let sp = spg.snippet_from_doc(doc).to_html();
-                if sp == "" {
-                    value_str
-                } else {
-                    sp
+        options: &SnippetOptions,
+    ) -> Highlighted {
+        let text = field_value.as_text().unwrap();
+        let fragments = match sp_gen {
+            Some(spg) => snippet::fragments(spg, text, options.fragments),
+            None => vec![],
+        };
+        if fragments.is_empty() {
+            return Highlighted::plain(snippet::lead(text, options.length));
+        }
+        Highlighted::join(fragments)
+    }
+
+    pub fn archive(&self, opts: &SearchOptions) -> Vec<YearCount> {
+        archive::histogram(&self.reader.searcher(), opts.tz.unwrap_or(UtcOffset::UTC))
+    }
+
+    pub fn suggest(&self, prefix: &str, limit: usize) -> Vec<Suggestion> {
+        self.suggester
+            .suggest(&self.reader.searcher(), prefix, limit)
+    }
+
+    /// Relevance, multiplied by `recency_factor`.
+    fn relevance_key(
+        &self,
+    ) -> impl Fn(&SegmentReader) -> Box<dyn FnMut(DocId, Score) -> Score> + Send + Sync {
+        let boost = self.boost.clone();
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        move |segment: &SegmentReader| {
+            let dates = segment.fast_fields().date("date").ok();
+            let boost = boost.clone();
+            Box::new(move |doc: DocId, score: Score| {
+                let date = match &dates {
+                    Some(dates) if boost.recency > 0.0 => dates.first(doc),
+                    _ => None,
+                };
+                match date {
+                    Some(date) => score * recency_factor(&boost, now, date),
+                    None => score,
                 }
+            })
+        }
+    }
+
+    /// Why `doc` matches `query` and the score it gets in `sort` order,
+    /// including the recency boost of the relevance order.
+    pub fn explain(&self, query: &dyn Query, doc: DocAddress, sort: Sort) -> Option<Explanation> {
+        let searcher = self.reader.searcher();
+        let explanation = query.explain(&searcher, doc).ok()?;
+        let date = searcher
+            .doc(doc)
+            .ok()?
+            .get_first(self.fields.date)
+            .and_then(|v| v.as_date());
+        match date {
+            Some(date) if sort == Sort::Relevance && self.boost.recency > 0.0 => {
+                let now = OffsetDateTime::now_utc().unix_timestamp();
+                let factor = recency_factor(&self.boost, now, date);
+                let mut boosted =
+                    Explanation::new("Recency boost, product of:", explanation.value() * factor);
+                boosted.add_detail(explanation);
+                boosted.add_const("recency factor", factor);
+                Some(boosted)
+            }
+            _ => Some(explanation),
+        }
+    }
+
+    fn date_key(segment: &SegmentReader) -> Box<dyn FnMut(DocId, Score) -> i64> {
+        let dates = segment.fast_fields().date("date").ok();
+        Box::new(move |doc: DocId, _| {
+            dates
+                .as_ref()
+                .and_then(|dates| dates.first(doc))
+                .map_or(i64::MIN, |date| date.into_timestamp_secs())
+        })
+    }
+
+    fn first_str(column: &Option<StrColumn>, doc: DocId) -> String {
+        let mut text = String::new();
+        if let Some(column) = column {
+            if let Some(ord) = column.term_ords(doc).next() {
+                column.ord_to_str(ord, &mut text).ok();
             }
-            None => value_str,
         }
+        text
     }
 
-    pub fn make_paginate(&self, pages: Vec<i64>) -> TopDocs {
-        let page = pages[0] as usize;
-        let size = pages[1] as usize;
-        TopDocs::with_limit(size).and_offset((page - 1) * size)
+    // titles come out in reverse so the top docs are the first alphabetically
+    fn title_key(segment: &SegmentReader) -> Box<dyn FnMut(DocId, Score) -> Reverse<String>> {
+        let titles = segment.fast_fields().str("title_sort").ok().flatten();
+        Box::new(move |doc: DocId, _| Reverse(Self::first_str(&titles, doc)))
     }
-    pub fn make_paginate_with_sort(
+
+    /// Top docs by `key`, ties broken by url, skipping the docs up to and
+    /// including the one in `cursor`.
+    fn collect_page<K, F>(
         &self,
+        query: &dyn Query,
+        (size, offset): (usize, usize),
+        sort: Sort,
+        cursor: Option<&str>,
+        key: F,
+    ) -> Result<Page, String>
+    where
+        K: SortKey,
+        F: 'static + Send + Sync + Fn(&SegmentReader) -> Box<dyn FnMut(DocId, Score) -> K>,
+    {
+        let after = match cursor {
+            Some(token) => Some(cursor::decode::<K>(sort, token)?),
+            None => None,
+        };
+        let top = TopDocs::with_limit(size).and_offset(offset);
+        let collector = top.tweak_score(move |segment: &SegmentReader| {
+            let mut key = key(segment);
+            let urls = Arc::new(segment.fast_fields().str("url").ok().flatten());
+            let after = after.clone();
+            move |doc: DocId, score: Score| {
+                let sort_key = key(doc, score);
+                let url = UrlKey {
+                    urls: urls.clone(),
+                    doc,
+                };
+                let seen = match &after {
+                    Some((key, last)) => match sort_key.partial_cmp(key) {
+                        Some(Ordering::Greater) => true,
+                        Some(Ordering::Equal) => url.text() <= *last,
+                        _ => false,
+                    },
+                    None => false,
+                };
+                match seen {
+                    true => None,
+                    false => Some((sort_key, Reverse(url))),
+                }
+            }
+        });
+        let (top_docs, count) = self
+            .reader
+            .searcher()
+            .search(query, &(collector, Count))
+            .expect("Search Failed");
+        let hits: Vec<_> = top_docs
+            .into_iter()
+            .filter_map(|(sort_key, addr)| sort_key.map(|k| (k, addr)))
+            .collect();
+        let cursor = match hits.last() {
+            Some(((key, Reverse(url)), _)) if hits.len() == size => {
+                Some(cursor::encode(sort, key, &url.text()))
+            }
+            _ => None,
+        };
+        Ok(Page {
+            docs: hits.into_iter().map(|(_, addr)| addr).collect(),
+            count,
+            cursor,
+        })
+    }
+
+    /// The 1-based page number and the page size of `[page, size]`, the
+    /// `[search.paging]` default size when either is missing or not positive,
+    /// and at most the maximum size.
+    pub fn page_size(&self, pages: &[i64]) -> (usize, usize) {
+        let page = pages.first().map_or(1, |&page| page.max(1) as usize);
+        let size = match pages.get(1) {
+            Some(&size) if size > 0 => size as usize,
+            _ => self.paging.default_size,
+        };
+        (page, size.min(self.paging.max_size).max(1))
+    }
+
+    /// One page of the docs matching `query` in `sort` order, either by page
+    /// number or after the last hit of the previous page when `cursor` is set.
+    pub fn search_page(
+        &self,
+        query: &dyn Query,
         pages: Vec<i64>,
-    ) -> impl Collector<Fruit = Vec<(DateTime, DocAddress)>> {
-        let page = pages[0] as usize;
-        let size = pages[1] as usize;
-        TopDocs::with_limit(size)
-            .and_offset((page - 1) * size)
-            .order_by_fast_field("date", tantivy::Order::Desc)
+        sort: Sort,
+        cursor: Option<&str>,
+    ) -> Result<Page, String> {
+        let (page, size) = self.page_size(&pages);
+        // no page past the last doc, so a huge page number allocates nothing
+        let num_docs = self.reader.searcher().num_docs() as usize;
+        let top = match cursor {
+            Some(_) => (size, 0),
+            None => (size, (page - 1).saturating_mul(size).min(num_docs)),
+        };
+        match sort {
+            Sort::Relevance => self.collect_page(query, top, sort, cursor, self.relevance_key()),
+            Sort::Newest => self.collect_page(query, top, sort, cursor, Self::date_key),
+            Sort::Oldest => self.collect_page(query, top, sort, cursor, |segment| {
+                let mut date = Self::date_key(segment);
+                Box::new(move |doc, score| Reverse(date(doc, score)))
+            }),
+            Sort::Title => self.collect_page(query, top, sort, cursor, Self::title_key),
+        }
     }
 
     pub fn make_bool_query(&self, q_vecs: Vec<Box<dyn Query>>) -> BooleanQuery {
@@ -199,30 +776,288 @@ impl QuerySchema {
                 .collect::<Vec<(Occur, Box<dyn Query>)>>(),
         )
     }
-    pub fn new(path: &str) -> Self {
-        let index = Index::open_in_dir(path).unwrap();
+    pub fn new(path: &str, analyzer: TextAnalyzer, config: &Search) -> Self {
+        Self::from_index(Index::open_in_dir(path).unwrap(), analyzer, config)
+    }
+
+    fn from_index(index: Index, analyzer: TextAnalyzer, config: &Search) -> Self {
         let schema = index.schema();
-        let token = Self::tokenizer();
-        index.tokenizers().register("UTF-8", token.clone());
+        register_tokenizers(&index, &analyzer);
         let title = schema.get_field("title").unwrap();
         let content = schema.get_field("content").unwrap();
-        let query_parser = QueryParser::for_index(&index, vec![title, content]);
+        let tags = schema.get_field("tags").unwrap();
+        let category = schema.get_field("category").unwrap();
         Self {
             fields: Fields {
                 url: schema.get_field("url").unwrap(),
-                tags: schema.get_field("tags").unwrap(),
+                tags,
                 date: schema.get_field("date").unwrap(),
                 title,
                 content,
-                category: schema.get_field("category").unwrap(),
+                category,
+                pinyin: schema.get_field("pinyin").unwrap(),
+                tags_facet: schema.get_field("tags_facet").unwrap(),
+                category_facet: schema.get_field("category_facet").unwrap(),
             },
             schema,
-            query_parser,
             reader: index
                 .reader_builder()
                 .reload_policy(tantivy::ReloadPolicy::OnCommit)
                 .try_into()
                 .unwrap(),
+            analyzer,
+            synonyms: Arc::new(Synonyms::new(config.synonyms_path.clone())),
+            suggester: Arc::new(Suggester::default()),
+            boost: config.boost.clone(),
+            paging: config.paging.clone(),
         }
     }
 }
+
+// posts of `(title, category, tags)` one day apart, the first the oldest
+#[cfg(test)]
+fn test_schema(posts: &[(&str, &str, &[&str])]) -> QuerySchema {
+    let blogs = posts
+        .iter()
+        .enumerate()
+        .map(|(i, &(title, category, tags))| test_blog(i, title, "", category, tags))
+        .collect();
+    test_index(blogs, &Search::default())
+}
+
+// the `i`th post, a day newer than the one before it
+#[cfg(test)]
+pub(crate) fn test_blog(
+    i: usize,
+    title: &str,
+    content: &str,
+    category: &str,
+    tags: &[&str],
+) -> crate::migrate::init::Blog {
+    crate::migrate::init::Blog {
+        title: title.to_owned(),
+        content: content.to_owned(),
+        url: format!("https://a/{}", i),
+        date: DateTime::from_timestamp_secs(1_700_000_000 + i as i64 * 86400),
+        category: category.to_owned(),
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn test_index(blogs: Vec<crate::migrate::init::Blog>, config: &Search) -> QuerySchema {
+    use crate::{
+        config::read::Tokenizer,
+        migrate::init::{add_doc, build_schema},
+        tokenizer::build_analyzer,
+    };
+    let analyzer = build_analyzer(&Tokenizer::default());
+    let index = Index::create_in_ram(build_schema());
+    register_tokenizers(&index, &analyzer);
+    let mut writer = index.writer(15_000_000).unwrap();
+    for blog in blogs {
+        add_doc(index.schema(), &mut writer, blog);
+    }
+    writer.commit().unwrap();
+    QuerySchema::from_index(index, analyzer, config)
+}
+
+// titles of the first page of hits
+#[cfg(test)]
+fn test_titles(qs: &QuerySchema, query: &dyn Query, sort: Sort) -> Vec<String> {
+    let searcher = qs.reader.searcher();
+    let page = qs.search_page(query, vec![], sort, None).unwrap();
+    page.docs
+        .into_iter()
+        .map(|addr| {
+            let doc = searcher.doc(addr).unwrap();
+            doc.get_first(qs.fields.title)
+                .unwrap()
+                .as_text()
+                .unwrap()
+                .to_owned()
+        })
+        .collect()
+}
+
+#[test]
+fn test_prefix() {
+    let qs = test_schema(&[
+        ("Rust async book", "", &[]),
+        ("Rusty tools", "", &[]),
+        ("Go basics", "", &[]),
+    ]);
+    let titles = |keyword: &str, prefix: bool| {
+        let opts = SearchOptions {
+            prefix,
+            ..SearchOptions::default()
+        };
+        let keyword = vec![keyword.to_owned()];
+        let query = BooleanQuery::from(
+            qs.make_keyword_query(keyword, &opts)
+                .unwrap()
+                .into_iter()
+                .map(|q| (Occur::Must, q))
+                .collect::<Vec<_>>(),
+        );
+        test_titles(&qs, &query, Sort::Title)
+    };
+    assert!(titles("rus", false).is_empty());
+    assert_eq!(titles("rus", true), vec!["Rust async book", "Rusty tools"]);
+    // only the last word is a prefix, the ones before it must be whole
+    assert_eq!(titles("rust as", true), vec!["Rust async book"]);
+    assert!(titles("rus as", true).is_empty());
+    assert_eq!(titles("go", true), vec!["Go basics"]);
+}
+
+#[test]
+fn test_sort() {
+    let qs = test_schema(&[
+        ("b post", "", &[]),
+        ("C post", "", &[]),
+        ("a post", "", &[]),
+    ]);
+    let titles = |sort| test_titles(&qs, &AllQuery, sort);
+    assert_eq!(titles(Sort::Newest), vec!["a post", "C post", "b post"]);
+    assert_eq!(titles(Sort::Oldest), vec!["b post", "C post", "a post"]);
+    assert_eq!(titles(Sort::Title), vec!["a post", "b post", "C post"]);
+    // the cursor of a full page continues after its last hit
+    let first = qs
+        .search_page(&AllQuery, vec![1, 2], Sort::Oldest, None)
+        .unwrap();
+    let cursor = first.cursor.unwrap();
+    let rest = qs
+        .search_page(&AllQuery, vec![1, 2], Sort::Oldest, Some(&cursor))
+        .unwrap();
+    assert_eq!(rest.docs.len(), 1);
+    assert_eq!(rest.count, 3);
+    assert!(rest.cursor.is_none());
+}
+
+#[test]
+fn test_terms_query() {
+    let qs = test_schema(&[
+        ("Rust post", "Code", &["Rust"]),
+        ("Go post", "Code/Go", &["go"]),
+        ("Trip post", "Life", &["travel"]),
+    ]);
+    let titles = |terms: &[&str]| {
+        let mut box_qs = Vec::new();
+        qs.make_terms_query(terms.iter().map(|t| t.to_string()).collect(), &mut box_qs);
+        test_titles(&qs, &qs.make_bool_query(box_qs), Sort::Title)
+    };
+    assert_eq!(titles(&["tags:rust|go"]), vec!["Go post", "Rust post"]);
+    assert_eq!(titles(&["-category:life"]), vec!["Go post", "Rust post"]);
+    assert_eq!(titles(&["category:code", "-tags:go"]), vec!["Rust post"]);
+    assert_eq!(titles(&["-tags:zzz"]).len(), 3);
+    assert!(titles(&["tags:zzz|yyy"]).is_empty());
+    // without a value there is nothing to filter by
+    let mut box_qs = Vec::new();
+    qs.make_terms_query(vec!["tags".to_owned(), "-category".to_owned()], &mut box_qs);
+    assert!(box_qs.is_empty());
+    assert_eq!(titles(&["tags", "category:life"]), vec!["Trip post"]);
+}
+
+#[test]
+fn test_cursor_ties() {
+    let qs = test_schema(&[("a", "", &[]), ("b", "", &[]), ("c", "", &[])]);
+    // every doc scores the same, the cursors go by url
+    let (mut seen, mut cursor) = (Vec::new(), None);
+    loop {
+        let page = qs
+            .search_page(&AllQuery, vec![1, 1], Sort::Relevance, cursor.as_deref())
+            .unwrap();
+        seen.extend(page.docs);
+        match page.cursor {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    assert_eq!(seen.len(), 3);
+    seen.sort();
+    seen.dedup();
+    assert_eq!(seen.len(), 3);
+}
+
+#[test]
+fn test_page_size() {
+    let qs = test_schema(&[]);
+    assert_eq!(qs.page_size(&[]), (1, 8));
+    assert_eq!(qs.page_size(&[3]), (3, 8));
+    assert_eq!(qs.page_size(&[0, 0]), (1, 8));
+    assert_eq!(qs.page_size(&[-2, -5]), (1, 8));
+    assert_eq!(qs.page_size(&[2, 20]), (2, 20));
+    assert_eq!(qs.page_size(&[1, 1_000]), (1, 50));
+}
+
+#[test]
+fn test_pinyin_fallback() {
+    let qs = test_schema(&[("机器学习入门", "", &[]), ("Go basics", "", &[])]);
+    let fallback = |keyword: &str| {
+        let keyword = vec![keyword.to_owned()];
+        let mut box_qs = qs
+            .make_keyword_query(keyword.clone(), &SearchOptions::default())
+            .unwrap();
+        let before = format!("{:?}", box_qs);
+        qs.pinyin_fallback(&keyword, &mut box_qs);
+        let changed = format!("{:?}", box_qs) != before;
+        let titles = test_titles(&qs, &qs.make_bool_query(box_qs), Sort::Title);
+        (titles, changed)
+    };
+    assert_eq!(fallback("jqxx"), (vec!["机器学习入门".to_owned()], true));
+    assert_eq!(
+        fallback("jiqixuexi"),
+        (vec!["机器学习入门".to_owned()], true)
+    );
+    // regular hits keep the keyword query
+    assert_eq!(fallback("go"), (vec!["Go basics".to_owned()], false));
+}
+
+#[test]
+fn test_related() {
+    let qs = test_schema(&[
+        ("Rust async runtime", "", &["rust"]),
+        ("Go channels", "", &["go"]),
+        ("Rust async book", "", &["rust"]),
+        ("Rust macros", "", &[]),
+    ]);
+    assert!(qs.make_related_query("https://a/9").is_none());
+    let query = qs.make_related_query("https://a/0").unwrap();
+    let titles = test_titles(&qs, &query, Sort::Relevance);
+    assert_eq!(titles, vec!["Rust async book", "Rust macros"]);
+}
+
+#[test]
+fn test_boost() {
+    use crate::config::read::Boost;
+    let rank = |blogs, recency| {
+        let config = Search {
+            boost: Boost {
+                recency,
+                ..Boost::default()
+            },
+            ..Search::default()
+        };
+        let qs = test_index(blogs, &config);
+        let keyword = vec!["rust".to_owned()];
+        let box_qs = qs
+            .make_keyword_query(keyword, &SearchOptions::default())
+            .unwrap();
+        test_titles(&qs, &qs.make_bool_query(box_qs), Sort::Relevance)
+    };
+    // a title match outranks a content-only one
+    let blogs = vec![
+        test_blog(0, "Notes", "rust", "", &[]),
+        test_blog(1, "Rust", "", "", &[]),
+    ];
+    assert_eq!(rank(blogs, 0.0), vec!["Rust", "Notes"]);
+    // the newer of two equal matches wins once recency counts
+    let tied = || {
+        vec![
+            test_blog(0, "Rust old", "", "", &[]),
+            test_blog(1, "Rust new", "", "", &[]),
+        ]
+    };
+    assert_eq!(rank(tied(), 0.0), vec!["Rust old", "Rust new"]);
+    assert_eq!(rank(tied(), 1.0), vec!["Rust new", "Rust old"]);
+}
@@ -0,0 +1,299 @@
+use std::{cmp::Reverse, iter::once, ops::Range};
+
+use tantivy::SnippetGenerator;
+
+static SEPARATOR: &str = " … ";
+
+/// A piece of the text and the byte ranges of its highlighted words.
+pub struct Fragment<'a> {
+    pub text: &'a str,
+    pub highlighted: Vec<Range<usize>>,
+}
+
+/// Snippet text and the `[start, end)` char offsets of its highlighted words.
+#[derive(Debug, PartialEq)]
+pub struct Highlighted {
+    pub text: String,
+    pub ranges: Vec<[usize; 2]>,
+}
+
+fn escape(text: &str) -> String {
+    let mut html = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#x27;"),
+            _ => html.push(c),
+        }
+    }
+    html
+}
+
+impl Highlighted {
+    pub fn plain(text: String) -> Highlighted {
+        Highlighted {
+            text,
+            ranges: vec![],
+        }
+    }
+
+    /// Fragments joined by an ellipsis, overlapping highlights merged.
+    pub fn join(fragments: Vec<Fragment>) -> Highlighted {
+        let (mut text, mut bytes) = (String::new(), Vec::new());
+        for (i, fragment) in fragments.iter().enumerate() {
+            if i > 0 {
+                text.push_str(SEPARATOR);
+            }
+            let offset = text.len();
+            bytes.extend(
+                fragment
+                    .highlighted
+                    .iter()
+                    .map(|r| (offset + r.start, offset + r.end)),
+            );
+            text.push_str(fragment.text);
+        }
+        bytes.sort();
+        let mut merged: Vec<(usize, usize)> = Vec::new();
+        for (start, end) in bytes {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        let chars = |byte: usize| text[..byte].chars().count();
+        let ranges = merged.iter().map(|&(s, e)| [chars(s), chars(e)]).collect();
+        Highlighted { text, ranges }
+    }
+
+    /// Escaped html with each highlight wrapped in `prefix` and `postfix`.
+    pub fn to_html(&self, prefix: &str, postfix: &str) -> String {
+        let chars: Vec<char> = self.text.chars().collect();
+        let text = |from: usize, to: usize| escape(&chars[from..to].iter().collect::<String>());
+        let mut html = String::new();
+        let mut from = 0;
+        for &[start, end] in &self.ranges {
+            html.push_str(&text(from, start));
+            html.push_str(prefix);
+            html.push_str(&text(start, end));
+            html.push_str(postfix);
+            from = end;
+        }
+        html.push_str(&text(from, chars.len()));
+        html
+    }
+}
+
+/// Up to `count` non-overlapping fragments of `text` with highlights, the
+/// ones with the most highlighted words picked first and returned in the
+/// order they appear in the text.
+pub fn fragments<'a>(
+    generator: &SnippetGenerator,
+    text: &'a str,
+    count: usize,
+) -> Vec<Fragment<'a>> {
+    // byte offset and text of the parts not yet cut into a fragment
+    let mut parts = vec![(0, text)];
+    let mut found: Vec<(usize, Fragment)> = Vec::new();
+    while found.len() < count {
+        let best = parts
+            .iter()
+            .enumerate()
+            .map(|(i, &(_, part))| (i, generator.snippet(part)))
+            .filter(|(_, snippet)| !snippet.is_empty())
+            .max_by_key(|(i, snippet)| (snippet.highlighted().len(), Reverse(*i)));
+        let (i, snippet) = match best {
+            Some(best) => best,
+            None => break,
+        };
+        let (offset, part) = parts.remove(i);
+        let start = part.find(snippet.fragment()).unwrap_or(0);
+        // the fragment ends with its last token, which can be one of the
+        // shorter words inside a highlighted long word
+        let end = snippet
+            .highlighted()
+            .iter()
+            .map(|r| r.end)
+            .chain(once(snippet.fragment().len()))
+            .max()
+            .map_or(start, |len| (start + len).min(part.len()));
+        parts.push((offset, &part[..start]));
+        parts.push((offset + end, &part[end..]));
+        parts.retain(|(_, p)| !p.trim().is_empty());
+        parts.sort_by_key(|&(offset, _)| offset);
+        let fragment = Fragment {
+            text: &part[start..end],
+            highlighted: snippet.highlighted().to_vec(),
+        };
+        found.push((offset + start, fragment));
+    }
+    found.sort_by_key(|(offset, _)| *offset);
+    found.into_iter().map(|(_, fragment)| fragment).collect()
+}
+
+fn ends_sentence(c: char, next: Option<char>) -> bool {
+    match c {
+        '。' | '！' | '？' | '…' => true,
+        '.' | '!' | '?' => next.is_none_or(char::is_whitespace),
+        _ => false,
+    }
+}
+
+/// The first paragraph of `text`, cut after the last whole sentence that fits
+/// in `max_bytes`, or at the last space when not even one does. Bytes, like
+/// the fragments tantivy cuts.
+pub fn lead(text: &str, max_bytes: usize) -> String {
+    let paragraph = text
+        .split("\n\n")
+        .map(str::trim)
+        .find(|p| !p.is_empty())
+        .unwrap_or("");
+    let mut chars = paragraph.char_indices().peekable();
+    let (mut sentence_end, mut space) = (None, None);
+    while let Some((i, c)) = chars.next() {
+        if i + c.len_utf8() > max_bytes {
+            let cut = sentence_end.or(space).unwrap_or(i);
+            let lead = paragraph[..cut].trim_end();
+            return match sentence_end {
+                Some(_) => lead.to_owned(),
+                None => format!("{}…", lead),
+            };
+        }
+        if ends_sentence(c, chars.peek().map(|&(_, next)| next)) {
+            sentence_end = Some(i + c.len_utf8());
+        } else if c.is_whitespace() {
+            space = Some(i);
+        }
+    }
+    paragraph.to_owned()
+}
+
+#[test]
+fn test_lead() {
+    let text = "\nRust 1.0 is out. It is fast! And safe.\n\nSecond paragraph.";
+    assert_eq!(lead(text, 100), "Rust 1.0 is out. It is fast! And safe.");
+    assert_eq!(lead(text, 30), "Rust 1.0 is out. It is fast!");
+    assert_eq!(lead(text, 12), "Rust 1.0 is…");
+    // three bytes a char
+    assert_eq!(lead("机器学习入门。深度学习", 24), "机器学习入门。");
+    assert_eq!(lead("机器学习入门", 12), "机器学习…");
+    assert_eq!(lead("机器学习入门", 14), "机器学习…");
+}
+
+#[test]
+fn test_fragments() {
+    use tantivy::{doc, query::TermQuery, schema::*, Index};
+    let mut builder = Schema::builder();
+    let content = builder.add_text_field("content", TEXT);
+    let index = Index::create_in_ram(builder.build());
+    let text = "rust is fast. go is simple. python is slow. rust is safe.";
+    let mut writer = index.writer(15_000_000).unwrap();
+    writer.add_document(doc!(content => text)).unwrap();
+    writer.commit().unwrap();
+    let query = TermQuery::new(
+        Term::from_field_text(content, "rust"),
+        IndexRecordOption::WithFreqs,
+    );
+    let searcher = index.reader().unwrap().searcher();
+    let mut generator = SnippetGenerator::create(&searcher, &query, content).unwrap();
+    generator.set_max_num_chars(14);
+    let html = |n| {
+        fragments(&generator, text, n)
+            .into_iter()
+            .map(|f| Highlighted::join(vec![f]).to_html("<b>", "</b>"))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(html(1).len(), 1);
+    let joined = Highlighted::join(fragments(&generator, text, 3));
+    assert_eq!(joined.text, "rust is fast … rust is safe");
+    assert_eq!(joined.ranges, vec![[0, 4], [15, 19]]);
+    assert_eq!(
+        Highlighted::plain("a<b".to_owned()).to_html("<b>", "</b>"),
+        "a&lt;b"
+    );
+    assert_eq!(html(3), vec!["<b>rust</b> is fast", "<b>rust</b> is safe"]);
+}
+
+#[test]
+fn test_fragments_long_words() {
+    use crate::tokenizer::{DictTokenizer, Dictionary};
+    use std::sync::Arc;
+    use tantivy::{doc, query::TermQuery, schema::*, tokenizer::TextAnalyzer, Index};
+    let mut dict = Dictionary::default();
+    dict.load_from(
+        "中华 10\n人民 10\n共和国 10\n中华人民共和国 1000\n机器 10\n机器学习 1000\n".as_bytes(),
+    );
+    let indexing = TextFieldIndexing::default()
+        .set_tokenizer("dict")
+        .set_index_option(IndexRecordOption::WithFreqsAndPositions);
+    let mut builder = Schema::builder();
+    let content = builder.add_text_field(
+        "content",
+        TextOptions::default().set_indexing_options(indexing),
+    );
+    let index = Index::create_in_ram(builder.build());
+    let analyzer = TextAnalyzer::builder(DictTokenizer {
+        dict: Arc::new(dict),
+    })
+    .build();
+    index.tokenizers().register("dict", analyzer);
+    // a fragment starts at the long word just past the first 300 bytes
+    let text = format!("{}中华人民共和国成立", "好".repeat(94));
+    let mut writer = index.writer(15_000_000).unwrap();
+    for text in [text.as_str(), "学好机器学习"] {
+        writer.add_document(doc!(content => text)).unwrap();
+    }
+    writer.commit().unwrap();
+    let searcher = index.reader().unwrap().searcher();
+    let snippet = |text: &str, word: &str| {
+        let query = TermQuery::new(
+            Term::from_field_text(content, word),
+            IndexRecordOption::WithFreqs,
+        );
+        let generator = SnippetGenerator::create(&searcher, &query, content).unwrap();
+        Highlighted::join(fragments(&generator, text, 1)).to_html("[", "]")
+    };
+    assert_eq!(snippet(&text, "中华人民共和国"), "[中华人民共和国]成立");
+    assert_eq!(snippet(&text, "人民"), "中华[人民]共和国成立");
+    // the last word inside 机器学习 ends before it does
+    assert_eq!(snippet("学好机器学习", "机器学习"), "学好[机器学习]");
+}
+
+#[test]
+fn test_length_cjk() {
+    use crate::tokenizer::{DictTokenizer, Dictionary};
+    use std::sync::Arc;
+    use tantivy::{doc, query::TermQuery, schema::*, tokenizer::TextAnalyzer, Index};
+    let indexing = TextFieldIndexing::default()
+        .set_tokenizer("dict")
+        .set_index_option(IndexRecordOption::WithFreqsAndPositions);
+    let mut builder = Schema::builder();
+    let content = builder.add_text_field(
+        "content",
+        TextOptions::default().set_indexing_options(indexing),
+    );
+    let index = Index::create_in_ram(builder.build());
+    let analyzer = TextAnalyzer::builder(DictTokenizer {
+        dict: Arc::new(Dictionary::default()),
+    })
+    .build();
+    index.tokenizers().register("dict", analyzer);
+    let text = "学".repeat(40);
+    let mut writer = index.writer(15_000_000).unwrap();
+    writer.add_document(doc!(content => text.as_str())).unwrap();
+    writer.commit().unwrap();
+    let searcher = index.reader().unwrap().searcher();
+    let query = TermQuery::new(
+        Term::from_field_text(content, "学"),
+        IndexRecordOption::WithFreqs,
+    );
+    let mut generator = SnippetGenerator::create(&searcher, &query, content).unwrap();
+    generator.set_max_num_chars(30);
+    // both count bytes, ten chars of three bytes each
+    let fragment = Highlighted::join(fragments(&generator, &text, 1)).text;
+    assert_eq!(fragment, "学".repeat(10));
+    assert_eq!(lead(&text, 30), format!("{}…", "学".repeat(10)));
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use levenshtein_automata::{Distance, LevenshteinAutomatonBuilder, DFA, SINK_STATE};
+use tantivy::{
+    schema::{Field, Term},
+    Searcher,
+};
+use tantivy_fst::Automaton;
+
+struct Dfa(DFA);
+
+impl Automaton for Dfa {
+    type State = u32;
+
+    fn start(&self) -> u32 {
+        self.0.initial_state()
+    }
+
+    fn is_match(&self, state: &u32) -> bool {
+        matches!(self.0.distance(*state), Distance::Exact(_))
+    }
+
+    fn can_match(&self, state: &u32) -> bool {
+        *state != SINK_STATE
+    }
+
+    fn accept(&self, state: &u32, byte: u8) -> u32 {
+        self.0.transition(*state, byte)
+    }
+}
+
+/// Closest indexed term to a word that is in none of `fields`, by the
+/// smallest edit distance first and the highest document frequency next.
+pub fn correct(searcher: &Searcher, fields: &[Field], word: &str) -> Option<String> {
+    let indexed = |f: Field| {
+        searcher
+            .doc_freq(&Term::from_field_text(f, word))
+            .unwrap_or(0)
+            > 0
+    };
+    if fields.iter().any(|&f| indexed(f)) {
+        return None;
+    }
+    let distance = if word.chars().count() < 5 { 1 } else { 2 };
+    let dfa = Dfa(LevenshteinAutomatonBuilder::new(distance, true).build_dfa(word));
+    let mut candidates: HashMap<String, (u8, u64)> = HashMap::new();
+    for segment in searcher.segment_readers() {
+        for &field in fields {
+            let inverted = match segment.inverted_index(field) {
+                Ok(inverted) => inverted,
+                Err(_) => continue,
+            };
+            let mut stream = match inverted.terms().search(&dfa).into_stream() {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            while let Some((key, info)) = stream.next() {
+                let (term, d) = match (std::str::from_utf8(key), dfa.0.eval(key)) {
+                    (Ok(term), Distance::Exact(d)) => (term, d),
+                    _ => continue,
+                };
+                candidates.entry(term.to_owned()).or_insert((d, 0)).1 += info.doc_freq as u64;
+            }
+        }
+    }
+    candidates
+        .into_iter()
+        .min_by(|a, b| (a.1 .0, b.1 .1, &a.0).cmp(&(b.1 .0, a.1 .1, &b.0)))
+        .map(|(term, _)| term)
+}
+
+#[test]
+fn test_correct() {
+    use tantivy::{doc, schema::Schema, schema::TEXT, Index};
+    let mut builder = Schema::builder();
+    let content = builder.add_text_field("content", TEXT);
+    let index = Index::create_in_ram(builder.build());
+    let mut writer = index.writer(15_000_000).unwrap();
+    writer
+        .add_document(doc!(content => "kubernetes tantivy"))
+        .unwrap();
+    writer
+        .add_document(doc!(content => "tantivy tantive"))
+        .unwrap();
+    writer.commit().unwrap();
+    let searcher = index.reader().unwrap().searcher();
+    let correct = |w: &str| correct(&searcher, &[content], w);
+    assert_eq!(correct("tantivi"), Some("tantivy".to_owned()));
+    assert_eq!(correct("kubernets"), Some("kubernetes".to_owned()));
+    assert_eq!(correct("tantivy"), None);
+    assert_eq!(correct("golang"), None);
+}
@@ -0,0 +1,143 @@
+use std::{collections::BTreeMap, sync::RwLock};
+
+use serde::Serialize;
+use tantivy::{Searcher, SegmentId};
+use tantivy_fst::{IntoStreamer, Map, Streamer};
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub text: String,
+    pub kind: &'static str,
+    pub count: u64,
+}
+
+/// Lowercased `text\0kind` keys in an FST, mapped to their suggestion.
+#[derive(Default)]
+struct SuggestIndex {
+    // segments and their deleted docs the index was built from
+    segments: Vec<(SegmentId, u32)>,
+    map: Option<Map<Vec<u8>>>,
+    entries: Vec<Suggestion>,
+}
+
+impl SuggestIndex {
+    /// Suggestions from `(text, kind, count)`, the counts of the same text and
+    /// kind added up.
+    fn build<I: Iterator<Item = (String, &'static str, u64)>>(values: I) -> SuggestIndex {
+        let mut counts: BTreeMap<String, Suggestion> = BTreeMap::new();
+        for (text, kind, count) in values.filter(|(t, _, _)| !t.is_empty()) {
+            counts
+                .entry(format!("{}\0{}", text.to_lowercase(), kind))
+                .or_insert_with(|| Suggestion {
+                    text,
+                    kind,
+                    count: 0,
+                })
+                .count += count;
+        }
+        let map = Map::from_iter(counts.keys().enumerate().map(|(i, k)| (k, i as u64)))
+            .expect("Cannot build suggestions.");
+        SuggestIndex {
+            segments: Vec::new(),
+            map: Some(map),
+            entries: counts.into_values().collect(),
+        }
+    }
+
+    fn complete(&self, prefix: &str, limit: usize) -> Vec<Suggestion> {
+        let map = match &self.map {
+            Some(m) => m,
+            None => return vec![],
+        };
+        let prefix = prefix.to_lowercase();
+        let mut found = Vec::new();
+        let mut stream = map.range().ge(&prefix).into_stream();
+        while let Some((key, idx)) = stream.next() {
+            if !key.starts_with(prefix.as_bytes()) {
+                break;
+            }
+            found.push(self.entries[idx as usize].clone());
+        }
+        found.sort_by(|a, b| b.count.cmp(&a.count).then(a.text.len().cmp(&b.text.len())));
+        found.truncate(limit);
+        found
+    }
+}
+
+// fast fields the suggestions come from and their kind
+static FIELDS: [(&str, &str); 3] = [
+    ("title", "title"),
+    ("tags", "tag"),
+    ("category", "category"),
+];
+
+/// Completions from titles, tags and categories, rebuilt on the first
+/// request after the segments of the index change.
+#[derive(Default)]
+pub struct Suggester {
+    index: RwLock<SuggestIndex>,
+}
+
+impl Suggester {
+    fn reload(&self, searcher: &Searcher) {
+        let mut segments: Vec<(SegmentId, u32)> = searcher
+            .segment_readers()
+            .iter()
+            .map(|s| (s.segment_id(), s.num_deleted_docs()))
+            .collect();
+        segments.sort();
+        if self.index.read().unwrap().segments == segments {
+            return;
+        }
+        let mut values = Vec::new();
+        for segment in searcher.segment_readers() {
+            for &(name, kind) in FIELDS.iter() {
+                let column = match segment.fast_fields().str(name) {
+                    Ok(Some(column)) => column,
+                    _ => continue,
+                };
+                let mut counts = vec![0u64; column.num_terms()];
+                for doc in segment.doc_ids_alive() {
+                    for ord in column.term_ords(doc) {
+                        counts[ord as usize] += 1;
+                    }
+                }
+                for (ord, count) in counts.into_iter().enumerate().filter(|&(_, c)| c > 0) {
+                    let mut text = String::new();
+                    column.ord_to_str(ord as u64, &mut text).ok();
+                    values.push((text, kind, count));
+                }
+            }
+        }
+        let mut index = SuggestIndex::build(values.into_iter());
+        index.segments = segments;
+        *self.index.write().unwrap() = index;
+    }
+
+    pub fn suggest(&self, searcher: &Searcher, prefix: &str, limit: usize) -> Vec<Suggestion> {
+        self.reload(searcher);
+        self.index.read().unwrap().complete(prefix, limit)
+    }
+}
+
+#[test]
+fn test_complete() {
+    let index = SuggestIndex::build(
+        vec![
+            ("Rust 异步编程", "title", 1),
+            ("rust", "tag", 1),
+            ("Rust", "tag", 1),
+            ("ruby", "tag", 1),
+            ("life", "category", 1),
+        ]
+        .into_iter()
+        .map(|(text, kind, count)| (text.to_owned(), kind, count)),
+    );
+    let texts = |s: Vec<Suggestion>| s.into_iter().map(|s| s.text).collect::<Vec<_>>();
+    assert_eq!(
+        texts(index.complete("Ru", 10)),
+        vec!["rust", "ruby", "Rust 异步编程"]
+    );
+    assert_eq!(index.complete("rus", 1)[0].count, 2);
+    assert!(index.complete("go", 10).is_empty());
+}
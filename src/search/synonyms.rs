@@ -0,0 +1,89 @@
+use std::{collections::HashMap, fs, sync::RwLock, time::SystemTime};
+
+#[derive(Default)]
+struct SynonymMap {
+    modified: Option<SystemTime>,
+    groups: HashMap<String, Vec<String>>,
+}
+
+/// Synonym groups read from a file with one comma separated group per line,
+/// e.g. `k8s, kubernetes`. The file is read again whenever it is modified.
+pub struct Synonyms {
+    path: Option<String>,
+    map: RwLock<SynonymMap>,
+}
+
+fn parse_groups(contents: &str) -> HashMap<String, Vec<String>> {
+    let mut groups = HashMap::new();
+    for line in contents.lines().filter(|l| !l.starts_with('#')) {
+        let words: Vec<String> = line
+            .split(',')
+            .map(|w| w.trim().to_lowercase())
+            .filter(|w| !w.is_empty())
+            .collect();
+        if words.len() < 2 {
+            continue;
+        }
+        for word in &words {
+            groups.insert(word.clone(), words.clone());
+        }
+    }
+    groups
+}
+
+impl Synonyms {
+    pub fn new(path: Option<String>) -> Self {
+        let synonyms = Synonyms {
+            path,
+            map: RwLock::new(SynonymMap::default()),
+        };
+        synonyms.reload();
+        synonyms
+    }
+
+    fn reload(&self) {
+        let path = match &self.path {
+            Some(p) => p,
+            None => return,
+        };
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        if self.map.read().unwrap().modified == modified {
+            return;
+        }
+        let groups = match fs::read_to_string(path) {
+            Ok(contents) => parse_groups(&contents),
+            Err(e) => {
+                println!("Error reading synonyms `{}`, {}", path, e);
+                HashMap::new()
+            }
+        };
+        println!("Loaded {} synonyms from `{}`.", groups.len(), path);
+        *self.map.write().unwrap() = SynonymMap { modified, groups };
+    }
+
+    /// The word together with its synonyms, the word alone if it has none.
+    pub fn expand(&self, word: &str) -> Vec<String> {
+        self.reload();
+        match self.map.read().unwrap().groups.get(word) {
+            Some(group) => group.clone(),
+            None => vec![word.to_owned()],
+        }
+    }
+}
+
+#[test]
+fn test_synonyms_reload() {
+    let path = std::env::temp_dir().join("search_api_synonyms.txt");
+    fs::write(&path, "k8s, kubernetes\njs,javascript\n").unwrap();
+    let synonyms = Synonyms::new(Some(path.to_str().unwrap().to_owned()));
+    assert_eq!(synonyms.expand("k8s"), vec!["k8s", "kubernetes"]);
+    assert_eq!(synonyms.expand("rust"), vec!["rust"]);
+
+    fs::write(&path, "数据库, database\n").unwrap();
+    let file = fs::File::options().write(true).open(&path).unwrap();
+    file.set_modified(SystemTime::now() + std::time::Duration::from_secs(10))
+        .unwrap();
+    assert_eq!(synonyms.expand("k8s"), vec!["k8s"]);
+    assert_eq!(synonyms.expand("database"), vec!["数据库", "database"]);
+    fs::remove_file(&path).unwrap();
+}
@@ -1,4 +1,44 @@
+pub mod dictionary;
+pub mod pinyin;
 pub mod segmentation;
+pub mod simplified;
 pub mod tokenstream;
 
-pub use segmentation::UTF8Tokenizer;
+use std::sync::Arc;
+
+use tantivy::tokenizer::{AsciiFoldingFilter, Language, Stemmer, StopWordFilter, TextAnalyzer};
+
+use crate::config::read::Tokenizer as TokenizerConfig;
+pub use dictionary::Dictionary;
+pub use segmentation::DictTokenizer;
+pub use simplified::SimplifiedFilter;
+
+fn load_dictionary(config: &TokenizerConfig) -> Dictionary {
+    let mut dict = match &config.dict_path {
+        Some(path) => Dictionary::load(path),
+        None => Dictionary::bundled(),
+    };
+    if let Some(path) = &config.user_dict_path {
+        dict.load_user(path);
+    }
+    dict
+}
+
+/// Builds the analyzer registered as `UTF-8`, shared by indexing and querying.
+pub fn build_analyzer(config: &TokenizerConfig) -> TextAnalyzer {
+    let mut builder = TextAnalyzer::builder(DictTokenizer {
+        dict: Arc::new(load_dictionary(config)),
+    })
+    .dynamic()
+    .filter_dynamic(AsciiFoldingFilter);
+    if config.traditional_to_simplified {
+        builder = builder.filter_dynamic(SimplifiedFilter);
+    }
+    if !config.stop_words.is_empty() {
+        builder = builder.filter_dynamic(StopWordFilter::remove(config.stop_words.clone()));
+    }
+    if config.stemming {
+        builder = builder.filter_dynamic(Stemmer::new(Language::English));
+    }
+    builder.build()
+}
@@ -0,0 +1,224 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    iter::once,
+    path::Path,
+};
+
+static BUNDLED: &str = include_str!("../../data/dict.txt");
+
+/// Word-frequency dictionary in the jieba format: `word [freq] [tag]` per line.
+///
+/// Every prefix of a word is stored with frequency 0, so building the DAG can
+/// stop as soon as a fragment is no longer a prefix of any word.
+#[derive(Default)]
+pub struct Dictionary {
+    words: HashMap<String, u64>,
+    total: u64,
+}
+
+pub fn is_han(c: char) -> bool {
+    matches!(c,
+        '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{20000}'..='\u{2A6DF}')
+}
+
+impl Dictionary {
+    /// The jieba dictionary shipped in `data/dict.txt`.
+    pub fn bundled() -> Dictionary {
+        let mut dict = Dictionary::default();
+        dict.load_from(BUNDLED.as_bytes());
+        dict
+    }
+
+    pub fn load(path: &str) -> Dictionary {
+        let mut dict = Dictionary::default();
+        dict.load_user(path);
+        dict
+    }
+
+    /// Loads extra words on top of the current dictionary, words without a
+    /// frequency get one that is high enough to keep them in one piece.
+    pub fn load_user(&mut self, path: &str) {
+        let file = File::open(Path::new(path))
+            .unwrap_or_else(|e| panic!("Cannot open dictionary `{}`, {}", path, e));
+        self.load_from(BufReader::new(file));
+    }
+
+    pub fn load_from<R: BufRead>(&mut self, reader: R) {
+        for line in reader.lines() {
+            let line = line.expect("Cannot read dictionary.");
+            let mut parts = line.split_whitespace();
+            let word = match parts.next() {
+                Some(w) if !w.starts_with('#') => w,
+                _ => continue,
+            };
+            let freq = match parts.next().and_then(|f| f.parse::<u64>().ok()) {
+                Some(f) => f,
+                None => self.suggest_freq(word),
+            };
+            self.add_word(word, freq);
+        }
+    }
+
+    pub fn add_word(&mut self, word: &str, freq: u64) {
+        let old = self.words.insert(word.to_owned(), freq).unwrap_or(0);
+        self.total = self.total - old + freq;
+        for (idx, _) in word.char_indices().skip(1) {
+            self.words.entry(word[..idx].to_owned()).or_insert(0);
+        }
+    }
+
+    fn suggest_freq(&self, word: &str) -> u64 {
+        let total = self.total.max(1) as f64;
+        let mut freq = total;
+        let mut pieces = Vec::new();
+        self.cut_han(word, &mut pieces);
+        for piece in pieces {
+            freq *= self.freq(piece) as f64 / total;
+        }
+        (freq as u64 + 1).max(self.freq(word))
+    }
+
+    fn freq(&self, word: &str) -> u64 {
+        match self.words.get(word) {
+            Some(&f) if f > 0 => f,
+            _ => 1,
+        }
+    }
+
+    /// Finds the max-probability path over all dictionary words of a Han run.
+    fn cut_han<'a>(&self, text: &'a str, result: &mut Vec<&'a str>) {
+        let mut bounds: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+        bounds.push(text.len());
+        let n = bounds.len() - 1;
+        let ln_total = (self.total.max(1) as f64).ln();
+        let mut route = vec![(0f64, 0usize); n + 1];
+        for i in (0..n).rev() {
+            let single = &text[bounds[i]..bounds[i + 1]];
+            route[i] = (
+                (self.freq(single) as f64).ln() - ln_total + route[i + 1].0,
+                i + 1,
+            );
+            for j in i + 2..=n {
+                match self.words.get(&text[bounds[i]..bounds[j]]) {
+                    None => break,
+                    Some(0) => continue,
+                    Some(&f) => {
+                        let prob = (f as f64).ln() - ln_total + route[j].0;
+                        if prob > route[i].0 {
+                            route[i] = (prob, j);
+                        }
+                    }
+                }
+            }
+        }
+        let mut i = 0;
+        while i < n {
+            let j = route[i].1;
+            result.push(&text[bounds[i]..bounds[j]]);
+            i = j;
+        }
+    }
+
+    /// Cuts Han runs by the dictionary and leaves the rest to `other`.
+    pub fn cut<'a>(&self, text: &'a str, other: fn(&'a str) -> Vec<&'a str>) -> Vec<&'a str> {
+        let mut result = Vec::new();
+        let mut start = 0;
+        let mut han = false;
+        for (idx, c) in text.char_indices() {
+            if is_han(c) != han {
+                self.cut_part(&text[start..idx], han, other, &mut result);
+                start = idx;
+                han = !han;
+            }
+        }
+        self.cut_part(&text[start..], han, other, &mut result);
+        result
+    }
+
+    /// Like `cut`, each Han word of more than two characters followed by the
+    /// dictionary words of two and three characters inside it in the order
+    /// they start, so 学习 also finds text cut as 机器学习 and the offsets
+    /// never go backwards.
+    pub fn cut_for_search<'a>(
+        &self,
+        text: &'a str,
+        other: fn(&'a str) -> Vec<&'a str>,
+    ) -> Vec<&'a str> {
+        let mut result = Vec::new();
+        for word in self.cut(text, other) {
+            result.push(word);
+            if !word.chars().all(is_han) {
+                continue;
+            }
+            let bounds: Vec<usize> = word
+                .char_indices()
+                .map(|(i, _)| i)
+                .chain(once(word.len()))
+                .collect();
+            let chars = bounds.len() - 1;
+            for i in 0..chars {
+                for n in (2..=3).filter(|&n| chars > n && i + n <= chars) {
+                    let sub = &word[bounds[i]..bounds[i + n]];
+                    if self.words.get(sub).is_some_and(|&f| f > 0) {
+                        result.push(sub);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    fn cut_part<'a>(
+        &self,
+        part: &'a str,
+        han: bool,
+        other: fn(&'a str) -> Vec<&'a str>,
+        result: &mut Vec<&'a str>,
+    ) {
+        if part.is_empty() {
+            return;
+        }
+        if han {
+            self.cut_han(part, result)
+        } else {
+            result.extend(other(part))
+        }
+    }
+}
+
+#[test]
+fn test_cut_han() {
+    use super::segmentation::cut_string;
+    let mut dict = Dictionary::default();
+    dict.load_from("机器 300\n学习 500\n机器学习 200\n方法 400\n的 1000\n".as_bytes());
+    assert_eq!(
+        dict.cut("机器学习的方法 Rust", cut_string),
+        vec!["机器学习", "的", "方法", " ", "Rust"]
+    );
+    assert_eq!(
+        dict.cut_for_search("机器学习的方法", cut_string),
+        vec!["机器学习", "机器", "学习", "的", "方法"]
+    );
+    dict.load_from("# comment\n学习方法\n".as_bytes());
+    assert_eq!(dict.cut("学习方法", cut_string), vec!["学习方法"]);
+    assert_eq!(dict.freq("#"), 1);
+}
+
+#[test]
+fn test_bundled() {
+    use super::segmentation::cut_string;
+    let dict = Dictionary::bundled();
+    assert_eq!(
+        dict.cut("我爱中华人民共和国", cut_string),
+        vec!["我", "爱", "中华人民共和国"]
+    );
+    assert_eq!(
+        dict.cut_for_search("中华人民共和国", cut_string),
+        vec!["中华人民共和国", "中华", "华人", "人民", "共和", "共和国"]
+    );
+}
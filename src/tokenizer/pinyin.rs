@@ -0,0 +1,100 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
+
+use super::{dictionary::is_han, segmentation::cut_string, simplified::to_simplified};
+
+static PINYIN_TABLE: &str = include_str!("../../data/pinyin.txt");
+static MAX_SYLLABLE_LEN: usize = 6;
+
+struct Pinyin {
+    readings: HashMap<char, &'static str>,
+    syllables: HashSet<&'static str>,
+}
+
+fn table() -> &'static Pinyin {
+    static TABLE: OnceLock<Pinyin> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut pinyin = Pinyin {
+            readings: HashMap::new(),
+            syllables: HashSet::new(),
+        };
+        for line in PINYIN_TABLE.lines().filter(|l| !l.starts_with('#')) {
+            if let Some((syllable, chars)) = line.split_once(' ') {
+                pinyin.syllables.insert(syllable);
+                for c in chars.chars() {
+                    pinyin.readings.entry(c).or_insert(syllable);
+                }
+            }
+        }
+        pinyin
+    })
+}
+
+/// Converts text to space separated full pinyin and initials, e.g.
+/// "机器学习" becomes ("ji qi xue xi", "j q x x"), other words are kept.
+pub fn to_pinyin(text: &str) -> (String, String) {
+    let readings = &table().readings;
+    let (mut full, mut initials) = (Vec::new(), Vec::new());
+    for word in cut_string(text) {
+        let mut chars = word.chars();
+        let syllable = match (chars.next(), chars.next()) {
+            (Some(c), None) if is_han(c) => match readings.get(&to_simplified(c)) {
+                Some(s) => s.to_string(),
+                None => continue,
+            },
+            (Some(c), _) if c.is_alphanumeric() => word.to_lowercase(),
+            _ => continue,
+        };
+        initials.push(syllable.chars().take(1).collect::<String>());
+        full.push(syllable);
+    }
+    (full.join(" "), initials.join(" "))
+}
+
+/// Splits "jiqixuexi" into the fewest known syllables, `None` when impossible.
+pub fn split_syllables(text: &str) -> Option<Vec<&str>> {
+    let syllables = &table().syllables;
+    if !text.is_ascii() {
+        return None;
+    }
+    let n = text.len();
+    // best[i]: (syllable count, end of first syllable) covering text[i..]
+    let mut best: Vec<Option<(usize, usize)>> = vec![None; n + 1];
+    best[n] = Some((0, n));
+    for i in (0..n).rev() {
+        for j in i + 1..=n.min(i + MAX_SYLLABLE_LEN) {
+            if let (true, Some((count, _))) = (syllables.contains(&text[i..j]), best[j]) {
+                if best[i].is_none_or(|(c, _)| count + 1 < c) {
+                    best[i] = Some((count + 1, j));
+                }
+            }
+        }
+    }
+    best[0]?;
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < n {
+        let j = best[i]?.1;
+        result.push(&text[i..j]);
+        i = j;
+    }
+    Some(result)
+}
+
+#[test]
+fn test_to_pinyin() {
+    assert_eq!(
+        to_pinyin("机器学习 Rust入门"),
+        (
+            "ji qi xue xi rust ru men".to_owned(),
+            "j q x x r r m".to_owned()
+        )
+    );
+    assert_eq!(
+        split_syllables("jiqixuexi"),
+        Some(vec!["ji", "qi", "xue", "xi"])
+    );
+    assert_eq!(split_syllables("rust"), None);
+}
@@ -1,9 +1,35 @@
+use std::sync::Arc;
+
 use tantivy::tokenizer::{Token, Tokenizer};
 
-use super::tokenstream::UTF8TokenStream;
+use super::{
+    dictionary::{is_han, Dictionary},
+    tokenstream::UTF8TokenStream,
+};
 
+/// Cuts Han characters by a word-frequency dictionary instead of one by one,
+/// the shorter words inside a long one come first at their own offsets.
 #[derive(Clone)]
-pub struct UTF8Tokenizer;
+pub struct DictTokenizer {
+    pub dict: Arc<Dictionary>,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() && !is_han(c)
+}
+
+// letters and digits, plus apostrophes inside a word like "don't"
+fn joins_word(words: &[char], idx: usize) -> bool {
+    match words[idx] {
+        '\'' | '\u{2019}' => {
+            idx > 0
+                && idx + 1 < words.len()
+                && is_word_char(words[idx - 1])
+                && is_word_char(words[idx + 1])
+        }
+        c => is_word_char(c),
+    }
+}
 
 pub fn cut_string(text: &str) -> Vec<&str> {
     let mut char_offset = 0usize;
@@ -13,20 +39,14 @@ pub fn cut_string(text: &str) -> Vec<&str> {
     while char_offset < words.len() {
         let mut byte_start = byte_offset;
         let mut char_start = char_offset;
-        let mut word = words[char_start];
-        if !word.is_ascii_alphabetic() {
-            byte_start += word.len_utf8();
+        if !joins_word(&words, char_start) {
+            byte_start += words[char_start].len_utf8();
             char_start += 1;
-        }
-        while word.is_ascii_alphabetic() {
-            if char_start == words.len() - 1 {
-                byte_start += word.len_utf8();
+        } else {
+            while char_start < words.len() && joins_word(&words, char_start) {
+                byte_start += words[char_start].len_utf8();
                 char_start += 1;
-                break;
             }
-            char_start += 1;
-            byte_start += word.len_utf8();
-            word = words[char_start];
         }
         result.push(&text[byte_offset..byte_start]);
         byte_offset = byte_start;
@@ -35,25 +55,36 @@ pub fn cut_string(text: &str) -> Vec<&str> {
     result
 }
 
-impl Tokenizer for UTF8Tokenizer {
+// `words` are slices of `text`, possibly overlapping
+fn make_token_stream(text: &str, words: Vec<&str>) -> UTF8TokenStream {
+    let mut tokens = Vec::with_capacity(words.len());
+    for word in words {
+        let offset = word.as_ptr() as usize - text.as_ptr() as usize;
+        let token = Token {
+            offset_from: offset,
+            offset_to: offset + word.len(),
+            position: offset,
+            text: word.to_lowercase(),
+            position_length: word.len(),
+        };
+        tokens.push(token);
+    }
+    UTF8TokenStream { tokens, offset: 0 }
+}
+
+impl Tokenizer for DictTokenizer {
     type TokenStream<'a> = UTF8TokenStream;
 
     fn token_stream<'a>(&'a mut self, text: &'a str) -> UTF8TokenStream {
-        let words = cut_string(text);
-        let mut offset = 0usize;
-        let mut tokens = Vec::with_capacity(words.len());
-        for word in words {
-            let next = offset + word.len();
-            let token = Token {
-                offset_from: offset,
-                offset_to: offset + word.len(),
-                position: offset,
-                text: word.to_lowercase(),
-                position_length: word.len(),
-            };
-            offset = next;
-            tokens.push(token);
-        }
-        UTF8TokenStream { tokens, offset: 0 }
+        make_token_stream(text, self.dict.cut_for_search(text, cut_string))
     }
 }
+
+#[test]
+fn test_cut_string() {
+    assert_eq!(
+        cut_string("Résumé don't 2024年"),
+        vec!["Résumé", " ", "don't", " ", "2024", "年"]
+    );
+    assert_eq!(cut_string("'rust's'"), vec!["'", "rust's", "'"]);
+}
@@ -0,0 +1,91 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use tantivy::tokenizer::{TokenFilter, Tokenizer};
+
+static T2S_TABLE: &str = include_str!("../../data/t2s.txt");
+
+fn table() -> &'static HashMap<char, char> {
+    static TABLE: OnceLock<HashMap<char, char>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut map = HashMap::new();
+        for pair in T2S_TABLE
+            .lines()
+            .filter(|l| !l.starts_with('#'))
+            .flat_map(|l| l.split_whitespace())
+        {
+            let mut chars = pair.chars();
+            if let (Some(t), Some(s)) = (chars.next(), chars.next()) {
+                // keep byte offsets of the converted text valid for the original
+                if t.len_utf8() == s.len_utf8() {
+                    map.insert(t, s);
+                }
+            }
+        }
+        map
+    })
+}
+
+pub fn to_simplified(c: char) -> char {
+    *table().get(&c).unwrap_or(&c)
+}
+
+/// Maps traditional Chinese to simplified before the text is segmented, so
+/// both scripts end up with the same terms.
+#[derive(Clone)]
+pub struct SimplifiedFilter;
+
+impl TokenFilter for SimplifiedFilter {
+    type Tokenizer<T: Tokenizer> = SimplifiedTokenizer<T>;
+
+    fn transform<T: Tokenizer>(self, tokenizer: T) -> SimplifiedTokenizer<T> {
+        SimplifiedTokenizer {
+            tokenizer,
+            buffer: String::new(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SimplifiedTokenizer<T> {
+    tokenizer: T,
+    buffer: String,
+}
+
+impl<T: Tokenizer> Tokenizer for SimplifiedTokenizer<T> {
+    type TokenStream<'a> = T::TokenStream<'a>;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> T::TokenStream<'a> {
+        self.buffer.clear();
+        self.buffer.extend(text.chars().map(to_simplified));
+        self.tokenizer.token_stream(&self.buffer)
+    }
+}
+
+#[test]
+fn test_simplified_filter() {
+    use super::{DictTokenizer, Dictionary};
+    use std::sync::Arc;
+    use tantivy::tokenizer::{TextAnalyzer, TokenStream};
+    // one token per character without dictionary words
+    let mut analyzer = TextAnalyzer::builder(DictTokenizer {
+        dict: Arc::new(Dictionary::default()),
+    })
+    .filter(SimplifiedFilter)
+    .build();
+    let mut stream = analyzer.token_stream("機器學習");
+    let mut tokens = Vec::new();
+    while let Some(token) = stream.next() {
+        tokens.push((token.text.clone(), token.offset_from));
+    }
+    assert_eq!(
+        tokens,
+        vec![
+            ("机".to_owned(), 0),
+            ("器".to_owned(), 3),
+            ("学".to_owned(), 6),
+            ("习".to_owned(), 9)
+        ]
+    );
+    let simplified: String = "標籤遊戲過濾贏謂鬧苧".chars().map(to_simplified).collect();
+    assert_eq!(simplified, "标签游戏过滤赢谓闹苎");
+}